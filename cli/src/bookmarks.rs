@@ -0,0 +1,106 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use db::models::Reference;
+
+use crate::paths;
+
+/// Bookmarked passages, stored one reference per line in a local file.
+pub struct Bookmarks {
+    path: PathBuf,
+    pub references: Vec<Reference>,
+}
+
+impl Bookmarks {
+    /// Default bookmarks file, in the user's data directory.
+    pub fn default_path() -> PathBuf {
+        paths::data_file("bookmarks")
+    }
+
+    /// Loads the bookmarks of a file, which doesn't need to exist yet.
+    ///
+    /// Lines that aren't references are skipped.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let references = match fs::read_to_string(path) {
+            Ok(contents) => contents.lines().filter_map(|l| l.parse().ok()).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            references,
+        })
+    }
+
+    /// Bookmarks a passage, or removes its bookmark when it has one.
+    ///
+    /// Returns whether the passage is now bookmarked.
+    pub fn toggle(&mut self, reference: Reference) -> io::Result<bool> {
+        let bookmarked = match self.references.iter().position(|r| *r == reference) {
+            Some(i) => {
+                self.references.remove(i);
+                false
+            }
+            None => {
+                self.references.push(reference);
+                true
+            }
+        };
+        self.save()?;
+        Ok(bookmarked)
+    }
+
+    /// Removes a bookmark by its position.
+    pub fn remove(&mut self, index: usize) -> io::Result<()> {
+        if index < self.references.len() {
+            self.references.remove(index);
+        }
+        self.save()
+    }
+
+    /// Whether a passage is bookmarked.
+    pub fn contains(&self, reference: &Reference) -> bool {
+        self.references.contains(reference)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents: String = self.references.iter().map(|r| format!("{}\n", r)).collect();
+        fs::write(&self.path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn toggle() {
+        let dir = env::temp_dir().join(format!("biblers-bookmarks-{}", std::process::id()));
+        let path = dir.join("bookmarks");
+        let reference = |s: &str| s.parse::<Reference>().unwrap();
+
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        assert!(bookmarks.references.is_empty());
+        assert!(bookmarks.toggle(reference("John 3:16")).unwrap());
+        assert!(bookmarks.toggle(reference("Psalms 23")).unwrap());
+
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        assert_eq!(
+            bookmarks.references,
+            vec![reference("John 3:16"), reference("Psalms 23")]
+        );
+        assert!(!bookmarks.toggle(reference("John 3:16")).unwrap());
+        assert!(!Bookmarks::load(&path)
+            .unwrap()
+            .contains(&reference("John 3:16")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::Write;
+
+use rand::seq::SliceRandom;
+
+use db::models::{Book, Reference, Testament};
+use db::{DbError, SwordDrillable, VerseFormat};
+
+use crate::error::CliError;
+use crate::format::{Output, Passage};
+
+/// Result of a command, whose output is written as it goes.
+pub type CommandResult = Result<(), CliError>;
+
+/// Looks a book up by its name or an abbreviation, suggesting the books
+/// it may be a misspelling of when there is none.
+pub async fn find_book(drill: &dyn SwordDrillable, name: &str) -> Result<Book, CliError> {
+    match drill.book(name).await {
+        Ok((book, _)) => Ok(book),
+        Err(DbError::BookNotFound { book }) => Err(book_not_found(drill, book).await),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Explains that there is no book of a name, with the books it may be a
+/// misspelling of.
+pub async fn book_not_found(drill: &dyn SwordDrillable, book: String) -> CliError {
+    match drill.all_books().await {
+        Ok(books) => CliError::BookNotFound {
+            suggestions: suggestions(&book, &books),
+            book,
+        },
+        Err(e) => e.into(),
+    }
+}
+
+/// Names of the books closest to a misspelled name, best first.
+fn suggestions(name: &str, books: &[Book]) -> Vec<String> {
+    let name = name.to_lowercase();
+    let mut scored: Vec<(f64, &Book)> = books
+        .iter()
+        .map(|b| (strsim::jaro_winkler(&name, &b.name.to_lowercase()), b))
+        .filter(|(score, _)| *score > 0.8)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(3)
+        .map(|(_, b)| b.name.clone())
+        .collect()
+}
+
+/// Looks the verses of a passage up, failing when its chapter or verses
+/// don't exist.
+pub async fn passage(
+    drill: &dyn SwordDrillable,
+    reference: &Reference,
+    format: VerseFormat,
+) -> Result<Passage, CliError> {
+    let book = find_book(drill, &reference.book).await?;
+    if reference.chapter < 1 || reference.chapter > book.chapter_count {
+        return Err(CliError::ChapterOutOfRange {
+            book: book.name,
+            chapter: reference.chapter,
+            count: book.chapter_count,
+        });
+    }
+
+    let reference = Reference {
+        book: book.name.clone(),
+        chapter: reference.chapter,
+        verses: reference.verses.clone(),
+    };
+    let (_, verses) = drill.verses(&reference, format).await?;
+    if verses.is_empty() {
+        let chapter = Reference {
+            verses: None,
+            ..reference.clone()
+        };
+        let (_, all) = drill.verses(&chapter, VerseFormat::PlainText).await?;
+        return Err(CliError::VerseOutOfRange {
+            reference: reference.to_string(),
+            chapter: chapter.to_string(),
+            count: all.len(),
+        });
+    }
+
+    Ok(Passage {
+        book,
+        reference,
+        verses,
+    })
+}
+
+/// Prints a passage.
+pub async fn read(
+    drill: &dyn SwordDrillable,
+    reference: &Reference,
+    output: &Output,
+    out: &mut impl Write,
+) -> CommandResult {
+    let passage = passage(drill, reference, output.verse_format()).await?;
+    output.write(&passage, out)?;
+    Ok(())
+}
+
+/// Prints the verses matching a full-text search, best matches first.
+pub async fn search(
+    drill: &dyn SwordDrillable,
+    query: &str,
+    out: &mut impl Write,
+) -> CommandResult {
+    for (verse, book) in drill.search(query).await? {
+        writeln!(
+            out,
+            "{} {}:{}\t{}",
+            book.name,
+            verse.chapter,
+            verse.verse,
+            plain_text(&verse.words)
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints every book with its number of chapters.
+pub async fn books(drill: &dyn SwordDrillable, out: &mut impl Write) -> CommandResult {
+    for book in drill.all_books().await? {
+        writeln!(out, "{}\t{}", book.name, book.chapter_count)?;
+    }
+    Ok(())
+}
+
+/// Prints a book's chapters with their number of verses.
+pub async fn book(drill: &dyn SwordDrillable, name: &str, out: &mut impl Write) -> CommandResult {
+    let book = find_book(drill, name).await?;
+    let testament = match book.testament {
+        Testament::Old => "Old Testament",
+        Testament::New => "New Testament",
+    };
+    writeln!(out, "{} ({})", book.name, testament)?;
+
+    for chapter in 1..=book.chapter_count {
+        let reference = Reference {
+            book: book.name.clone(),
+            chapter,
+            verses: None,
+        };
+        let (_, verses) = drill.verses(&reference, VerseFormat::PlainText).await?;
+        writeln!(out, "{}\t{} verses", chapter, verses.len())?;
+    }
+    Ok(())
+}
+
+/// Prints a random verse.
+///
+/// Every verse of the Bible is as likely to be picked.
+pub async fn random(
+    drill: &dyn SwordDrillable,
+    output: &Output,
+    out: &mut impl Write,
+) -> CommandResult {
+    let ids = drill.verse_ids().await?;
+    let id = ids
+        .choose(&mut rand::thread_rng())
+        .ok_or_else(|| CliError::Database("There are no verses in the database.".into()))?;
+
+    let (book, verse) = drill.verse(*id).await?;
+    let reference = Reference {
+        book: book.name,
+        chapter: verse.chapter,
+        verses: Some(verse.verse..=verse.verse),
+    };
+    read(drill, &reference, output, out).await
+}
+
+/// Turns the escaped HTML of a search result into plain text.
+pub fn plain_text(html: &str) -> String {
+    html.replace("<em>", "")
+        .replace("</em>", "")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn book_suggestions() {
+        let books: Vec<Book> = ["Genesis", "Job", "Joel", "John", "Jonah", "Jude"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Book {
+                id: i as i32 + 1,
+                name: name.to_string(),
+                chapter_count: 1,
+                testament: Testament::Old,
+            })
+            .collect();
+
+        assert_eq!(suggestions("Jhon", &books)[0], "John");
+        assert_eq!(suggestions("genisis", &books), ["Genesis"]);
+        assert!(suggestions("Hezekiah", &books).is_empty());
+    }
+
+    #[test]
+    fn search_results() {
+        assert_eq!(
+            plain_text("the <em>light</em> &amp; &lt;darkness&gt;"),
+            "the light & <darkness>"
+        );
+    }
+}
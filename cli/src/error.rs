@@ -0,0 +1,196 @@
+use std::io;
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use thiserror::Error;
+
+use db::DbError;
+use web::export::ExportError;
+use web::RunError;
+
+/// Exit code of unexpected errors, such as failing to write the output.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code of arguments that don't parse, references included.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Exit code of a book, chapter or verse that doesn't exist.
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+/// Exit code of a database that is missing or can't be read.
+pub const EXIT_DATABASE: i32 = 4;
+
+/// Exit codes, as shown by `--help`.
+pub const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  Unexpected error, such as failing to write the output
+  2  Invalid arguments, including references that don't parse
+  3  Book, chapter or verse not found
+  4  Database missing or unreadable";
+
+/// Error of a command, explained to the user.
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("No Bible database was found.")]
+    DatabaseMissing { searched: Vec<PathBuf> },
+
+    #[error("{0}")]
+    Database(String),
+
+    #[error("'{reference}' is not a Bible reference.")]
+    InvalidReference { reference: String },
+
+    #[error("There is no book called '{book}'.")]
+    BookNotFound {
+        book: String,
+        /// Names of the books it may be a misspelling of, best first.
+        suggestions: Vec<String>,
+    },
+
+    #[error("There is no chapter {chapter} in {book}.")]
+    ChapterOutOfRange {
+        book: String,
+        chapter: i32,
+        count: i32,
+    },
+
+    #[error("There is no {reference}.")]
+    VerseOutOfRange {
+        reference: String,
+        /// Book and chapter of the reference.
+        chapter: String,
+        count: usize,
+    },
+
+    #[error("{0}")]
+    Serve(String),
+
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl CliError {
+    /// Exit code of the class of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidReference { .. } | Self::Serve(_) => EXIT_USAGE,
+            Self::BookNotFound { .. }
+            | Self::ChapterOutOfRange { .. }
+            | Self::VerseOutOfRange { .. } => EXIT_NOT_FOUND,
+            Self::DatabaseMissing { .. } | Self::Database(_) => EXIT_DATABASE,
+            Self::Io(_) | Self::Other(_) => EXIT_FAILURE,
+        }
+    }
+
+    /// Suggestion of what to do about the error, if there is one.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::DatabaseMissing { searched } => {
+                let searched: Vec<String> =
+                    searched.iter().map(|p| p.display().to_string()).collect();
+                Some(format!(
+                    "Build one with `cargo run -p db --bin build-db -- <path>`, or pass \
+                     --database or set DATABASE_URL to one that exists. Looked for: {}",
+                    searched.join(", ")
+                ))
+            }
+            Self::InvalidReference { .. } => Some(
+                "References look like \"John 3:16\", \"John 3:16-18\" or \"Psalms 23\".".into(),
+            ),
+            Self::BookNotFound { suggestions, .. } => Some(match suggestions.split_last() {
+                None => "Run `bible books` to list the books.".into(),
+                Some((last, [])) => format!("Did you mean {}?", last),
+                Some((last, others)) => format!("Did you mean {} or {}?", others.join(", "), last),
+            }),
+            Self::ChapterOutOfRange { book, count, .. } => {
+                Some(format!("{} has {} chapters.", book, count))
+            }
+            Self::VerseOutOfRange { chapter, count, .. } => {
+                Some(format!("{} has {} verses.", chapter, count))
+            }
+            Self::Database(_) | Self::Serve(_) | Self::Io(_) | Self::Other(_) => None,
+        }
+    }
+
+    /// Prints the error and its hint to stderr.
+    pub fn report(&self) {
+        eprintln!("error: {}", self);
+        if let Some(hint) = self.hint() {
+            eprintln!("hint: {}", hint);
+        }
+    }
+}
+
+impl From<DbError> for CliError {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::BookNotFound { book } => Self::BookNotFound {
+                book,
+                suggestions: Vec::new(),
+            },
+            DbError::InvalidReference { reference } => Self::InvalidReference { reference },
+            e => Self::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<RunError> for CliError {
+    fn from(e: RunError) -> Self {
+        match e {
+            RunError::Db(e) => e.into(),
+            RunError::Io(e) => Self::Io(e),
+            // The server's settings are invalid
+            e => Self::Serve(e.to_string()),
+        }
+    }
+}
+
+impl From<ExportError> for CliError {
+    fn from(e: ExportError) -> Self {
+        match e {
+            ExportError::Db(e) => e.into(),
+            ExportError::Io(e) => Self::Io(e),
+        }
+    }
+}
+
+impl From<ReadlineError> for CliError {
+    fn from(e: ReadlineError) -> Self {
+        match e {
+            ReadlineError::Io(e) => Self::Io(e),
+            e => Self::Other(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints() {
+        let not_found = |suggestions: &[&str]| CliError::BookNotFound {
+            book: "Jo".into(),
+            suggestions: suggestions.iter().map(|s| s.to_string()).collect(),
+        };
+
+        assert_eq!(
+            not_found(&[]).hint().unwrap(),
+            "Run `bible books` to list the books."
+        );
+        assert_eq!(not_found(&["John"]).hint().unwrap(), "Did you mean John?");
+        assert_eq!(
+            not_found(&["John", "Joel", "Job"]).hint().unwrap(),
+            "Did you mean John, Joel or Job?"
+        );
+        assert_eq!(not_found(&[]).exit_code(), EXIT_NOT_FOUND);
+        assert_eq!(
+            CliError::from(DbError::Other { cause: "".into() }).exit_code(),
+            EXIT_DATABASE
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use clap::Subcommand;
+
+use db::{DbError, SwordDrillable};
+use web::export::markdown::{self, Split};
+use web::export::text::{Chunks, TextFormat};
+use web::export::{epub, Range};
+
+use crate::commands::{self, CommandResult};
+use crate::error::CliError;
+
+/// Formats the Bible can be exported to.
+#[derive(Debug, Subcommand)]
+pub enum Export {
+    /// EPUB 3 book for e-readers
+    Epub {
+        /// Book, books or passage to export (e.g. "John",
+        /// "Genesis-Deuteronomy" or "Psalms 23") [default: the whole Bible]
+        #[clap(long)]
+        range: Option<String>,
+
+        /// File to write [default: named after the range]
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Markdown notes for an Obsidian-style vault
+    Markdown {
+        /// Book, books or passage to export [default: the whole Bible]
+        #[clap(long)]
+        range: Option<String>,
+
+        /// Directory of the notes [default: named after the range]
+        #[clap(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        /// Write a note per chapter or per book
+        #[clap(long, value_enum, default_value_t)]
+        split: Split,
+    },
+    /// Every verse as a line of text or a table, for data work
+    Text {
+        /// Format of the verses
+        #[clap(long, value_enum, default_value_t)]
+        format: TextFormat,
+
+        /// File to write [default: standard output]
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Finds the part of the Bible a range names, suggesting books for a
+/// misspelled one.
+async fn find_range(drill: &dyn SwordDrillable, range: Option<&str>) -> Result<Range, CliError> {
+    match Range::find(drill, range).await {
+        Ok(range) => Ok(range),
+        Err(DbError::BookNotFound { book }) => Err(commands::book_not_found(drill, book).await),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes every verse, in chunks as they are streamed from the database.
+async fn write_text(
+    drill: &dyn SwordDrillable,
+    format: TextFormat,
+    out: &mut dyn Write,
+) -> CommandResult {
+    let mut chunks = Chunks::new(drill, format).await?;
+    while let Some(chunk) = chunks.next().await {
+        out.write_all(chunk?.as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Exports a range of the Bible to a file, or the full text to standard
+/// output.
+pub async fn run(
+    drill: &dyn SwordDrillable,
+    export: Export,
+    out: &mut impl Write,
+) -> CommandResult {
+    match export {
+        Export::Epub { range, output } => {
+            let range = find_range(drill, range.as_deref()).await?;
+            let path = output.unwrap_or_else(|| format!("{}.epub", range.file_stem()).into());
+            let file = BufWriter::new(File::create(path)?);
+            epub::write(drill, &range, SystemTime::now(), file).await?;
+            Ok(())
+        }
+        Export::Markdown {
+            range,
+            output,
+            split,
+        } => {
+            let range = find_range(drill, range.as_deref()).await?;
+            let dir = output.unwrap_or_else(|| range.file_stem().into());
+            markdown::write(drill, &range, split, &dir).await?;
+            Ok(())
+        }
+        Export::Text { format, output } => match output {
+            Some(path) => write_text(drill, format, &mut BufWriter::new(File::create(path)?)).await,
+            None => write_text(drill, format, out).await,
+        },
+    }
+}
@@ -0,0 +1,435 @@
+use std::io::{self, Write};
+
+use clap::{Args, ValueEnum};
+use serde_json::json;
+
+use db::models::{Book, Reference, Verse};
+use db::VerseFormat;
+use web::format::{csv_field, escape};
+
+/// Translation cited with passages.
+const TRANSLATION: &str = "KJV";
+
+/// Format passages are printed in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Verse numbers and text separated by tabs
+    #[default]
+    Plain,
+    /// The book and verse models
+    Json,
+    /// Bold verse numbers
+    Markdown,
+    /// The formatted verses the web pages show
+    Html,
+    /// One `book,chapter,verse,text` row per verse
+    Csv,
+}
+
+/// Way a passage is cited, for pasting it into a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Citation {
+    /// Quoted text followed by the reference
+    Inline,
+    /// Text with a footnote marker and the reference as the footnote
+    Footnote,
+    /// Block quote attributed to the reference
+    Blockquote,
+}
+
+/// Options of how passages are printed.
+#[derive(Args, Clone, Debug, Default)]
+pub struct Output {
+    /// Output format
+    #[clap(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Cite the passage (plain, markdown and html)
+    #[clap(long, value_enum)]
+    pub citation: Option<Citation>,
+
+    /// Leave the verse numbers out (plain, markdown and html)
+    #[clap(long)]
+    pub no_numbers: bool,
+
+    /// Wrap plain text at a number of columns
+    #[clap(long, value_name = "N")]
+    pub wrap: Option<usize>,
+}
+
+/// Verses of a chapter, with the reference they were looked up with.
+pub struct Passage {
+    pub book: Book,
+    /// Reference with the canonical name of the book.
+    pub reference: Reference,
+    pub verses: Vec<Verse>,
+}
+
+impl Output {
+    /// Format of the verses to look up.
+    pub fn verse_format(&self) -> VerseFormat {
+        match self.format {
+            Format::Html => VerseFormat::Html,
+            _ => VerseFormat::PlainText,
+        }
+    }
+
+    /// Gets the option given that the format has no use for, if any.
+    ///
+    /// JSON and CSV hold the verses as data, so they can't be cited or
+    /// numbered otherwise, and only plain text is wrapped.
+    pub fn unsupported(&self) -> Option<&'static str> {
+        let data = matches!(self.format, Format::Json | Format::Csv);
+        if data && self.citation.is_some() {
+            Some("--citation")
+        } else if data && self.no_numbers {
+            Some("--no-numbers")
+        } else if self.wrap.is_some() && self.format != Format::Plain {
+            Some("--wrap")
+        } else {
+            None
+        }
+    }
+
+    /// Prints a passage.
+    pub fn write(&self, passage: &Passage, out: &mut impl Write) -> io::Result<()> {
+        match self.format {
+            Format::Plain | Format::Markdown => self.write_text(passage, out),
+            Format::Json => {
+                let value = json!({
+                    "reference": passage.reference.to_string(),
+                    "translation": TRANSLATION,
+                    "book": passage.book,
+                    "verses": passage.verses,
+                });
+                serde_json::to_writer_pretty(&mut *out, &value)?;
+                writeln!(out)
+            }
+            Format::Html => self.write_html(passage, out),
+            Format::Csv => {
+                writeln!(out, "book,chapter,verse,text")?;
+                for v in &passage.verses {
+                    writeln!(
+                        out,
+                        "{},{},{},{}",
+                        csv_field(&passage.book.name),
+                        v.chapter,
+                        v.verse,
+                        csv_field(&v.words)
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Lines of plain text or Markdown of a passage's verses.
+    fn lines(&self, passage: &Passage, width: Option<usize>) -> Vec<String> {
+        if self.no_numbers {
+            return vec![fill(words(&passage.verses).join(" "), width)];
+        }
+
+        passage
+            .verses
+            .iter()
+            .map(|v| match (self.format, width) {
+                (Format::Markdown, _) => format!("**{}** {}", v.verse, v.words),
+                (_, Some(w)) => {
+                    let number = v.verse.to_string();
+                    wrap(&format!("{} {}", number, v.words), w, number.len() + 1)
+                }
+                (_, None) => format!("{}\t{}", v.verse, v.words),
+            })
+            .collect()
+    }
+
+    /// Prints a passage as plain text or Markdown.
+    fn write_text(&self, passage: &Passage, out: &mut impl Write) -> io::Result<()> {
+        let markdown = self.format == Format::Markdown;
+        let attribution = format!("{} ({})", passage.reference, TRANSLATION);
+        // Markdown is reflowed by its renderer
+        let width = self.wrap.filter(|_| !markdown);
+
+        match self.citation {
+            None => {
+                if markdown {
+                    writeln!(out, "## {}\n", passage.reference)?;
+                } else {
+                    writeln!(out, "{}", passage.reference)?;
+                }
+                for line in self.lines(passage, width) {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            Some(Citation::Inline) => {
+                let text = format!(
+                    "\"{}\" ({} {})",
+                    words(&passage.verses).join(" "),
+                    passage.reference,
+                    TRANSLATION
+                );
+                writeln!(out, "{}", fill(text, width))?;
+            }
+            Some(Citation::Footnote) => {
+                let (marker, note) = if markdown {
+                    ("[^1]", "[^1]:")
+                } else {
+                    ("[1]", "[1]")
+                };
+                let lines = self.lines(passage, width);
+                let last = lines.len().saturating_sub(1);
+                for (i, line) in lines.iter().enumerate() {
+                    let marker = if i == last { marker } else { "" };
+                    writeln!(out, "{}{}", line, marker)?;
+                }
+                writeln!(out, "\n{} {}", note, attribution)?;
+            }
+            Some(Citation::Blockquote) => {
+                for line in self.lines(passage, width.map(|w| w.saturating_sub(2))) {
+                    for line in line.lines() {
+                        writeln!(out, "> {}", line)?;
+                    }
+                }
+                writeln!(out, ">\n> — {}", attribution)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a passage as HTML, with the formatting of the web pages.
+    fn write_html(&self, passage: &Passage, out: &mut impl Write) -> io::Result<()> {
+        let reference = escape(&passage.reference.to_string());
+        let cite = format!("<cite>{}</cite> ({})", reference, TRANSLATION);
+        let paragraphs: Vec<String> = passage
+            .verses
+            .iter()
+            .map(|v| {
+                if self.no_numbers {
+                    format!("<p id=\"v{}\">{}</p>", v.verse, v.words)
+                } else {
+                    format!("<p id=\"v{0}\"><sup>{0}</sup> {1}</p>", v.verse, v.words)
+                }
+            })
+            .collect();
+
+        match self.citation {
+            None => {
+                writeln!(out, "<h2>{}</h2>", reference)?;
+                for p in paragraphs {
+                    writeln!(out, "{}", p)?;
+                }
+            }
+            Some(Citation::Inline) => {
+                writeln!(
+                    out,
+                    "<q>{}</q> (<cite>{}</cite> {})",
+                    words(&passage.verses).join(" "),
+                    reference,
+                    TRANSLATION
+                )?;
+            }
+            Some(Citation::Footnote) => {
+                let last = paragraphs.len().saturating_sub(1);
+                for (i, p) in paragraphs.iter().enumerate() {
+                    if i == last {
+                        let marker = "<sup><a href=\"#note-1\">[1]</a></sup></p>";
+                        writeln!(out, "{}", p.replacen("</p>", marker, 1))?;
+                    } else {
+                        writeln!(out, "{}", p)?;
+                    }
+                }
+                writeln!(
+                    out,
+                    "<ol class=\"footnotes\"><li id=\"note-1\">{}</li></ol>",
+                    cite
+                )?;
+            }
+            Some(Citation::Blockquote) => {
+                writeln!(out, "<blockquote>")?;
+                for p in paragraphs {
+                    writeln!(out, "{}", p)?;
+                }
+                writeln!(out, "<footer>— {}</footer>\n</blockquote>", cite)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Words of every verse.
+fn words(verses: &[Verse]) -> Vec<&str> {
+    verses.iter().map(|v| v.words.as_str()).collect()
+}
+
+/// Wraps a paragraph when there is a width.
+fn fill(text: String, width: Option<usize>) -> String {
+    match width {
+        Some(width) => wrap(&text, width, 0),
+        None => text,
+    }
+}
+
+/// Wraps text at a number of columns, indenting the lines after the first.
+///
+/// Words longer than a line are left whole.
+fn wrap(text: &str, width: usize, indent: usize) -> String {
+    let mut wrapped = String::new();
+    let mut column = 0;
+    let mut empty = true;
+    for word in text.split_whitespace() {
+        let len = word.chars().count();
+        if !empty && column + 1 + len > width {
+            wrapped.push('\n');
+            wrapped.push_str(&" ".repeat(indent));
+            column = indent;
+            empty = true;
+        }
+        if !empty {
+            wrapped.push(' ');
+            column += 1;
+        }
+        wrapped.push_str(word);
+        column += len;
+        empty = false;
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use db::models::Testament;
+
+    use super::*;
+
+    fn passage() -> Passage {
+        let verse = |verse, words: &str| Verse {
+            id: verse,
+            book: 43,
+            chapter: 3,
+            verse,
+            words: words.to_string(),
+        };
+        Passage {
+            book: Book {
+                id: 43,
+                name: "John".to_string(),
+                chapter_count: 21,
+                testament: Testament::New,
+            },
+            reference: "John 3:16-17".parse().unwrap(),
+            verses: vec![
+                verse(
+                    16,
+                    "For God so loved the world, that he gave his only begotten Son.",
+                ),
+                verse(
+                    17,
+                    "For God sent not his Son into the world to condemn the world.",
+                ),
+            ],
+        }
+    }
+
+    fn output(output: Output) -> String {
+        let mut out = Vec::new();
+        output.write(&passage(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(
+            output(Output::default()),
+            "John 3:16-17\n\
+             16\tFor God so loved the world, that he gave his only begotten Son.\n\
+             17\tFor God sent not his Son into the world to condemn the world.\n"
+        );
+
+        let wrapped = output(Output {
+            wrap: Some(30),
+            ..Output::default()
+        });
+        assert!(wrapped.starts_with("John 3:16-17\n16 For God so loved the world,\n   that he"));
+        assert!(wrapped.lines().all(|l| l.chars().count() <= 30));
+
+        let text = output(Output {
+            no_numbers: true,
+            ..Output::default()
+        });
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("Son. For God sent"));
+    }
+
+    #[test]
+    fn citations() {
+        let cite = |format, citation| {
+            output(Output {
+                format,
+                citation: Some(citation),
+                ..Output::default()
+            })
+        };
+
+        assert!(cite(Format::Plain, Citation::Inline)
+            .ends_with("condemn the world.\" (John 3:16-17 KJV)\n"));
+        assert!(cite(Format::Markdown, Citation::Footnote)
+            .ends_with("condemn the world.[^1]\n\n[^1]: John 3:16-17 (KJV)\n"));
+        assert!(
+            cite(Format::Markdown, Citation::Blockquote).starts_with("> **16** For God so loved")
+        );
+        assert!(cite(Format::Html, Citation::Blockquote)
+            .ends_with("<footer>— <cite>John 3:16-17</cite> (KJV)</footer>\n</blockquote>\n"));
+    }
+
+    #[test]
+    fn unsupported() {
+        let unsupported = |output| Output::unsupported(&output);
+
+        assert_eq!(unsupported(Output::default()), None);
+        assert_eq!(
+            unsupported(Output {
+                citation: Some(Citation::Inline),
+                format: Format::Html,
+                no_numbers: true,
+                ..Output::default()
+            }),
+            None
+        );
+        assert_eq!(
+            unsupported(Output {
+                format: Format::Json,
+                citation: Some(Citation::Inline),
+                ..Output::default()
+            }),
+            Some("--citation")
+        );
+        assert_eq!(
+            unsupported(Output {
+                format: Format::Csv,
+                no_numbers: true,
+                ..Output::default()
+            }),
+            Some("--no-numbers")
+        );
+        assert_eq!(
+            unsupported(Output {
+                format: Format::Markdown,
+                wrap: Some(40),
+                ..Output::default()
+            }),
+            Some("--wrap")
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let csv = output(Output {
+            format: Format::Csv,
+            ..Output::default()
+        });
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "John,3,16,\"For God so loved the world, that he gave his only begotten Son.\""
+        );
+    }
+}
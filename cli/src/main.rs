@@ -1,50 +1,267 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 
-use std::env;
-use std::io::{self, Write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
 use dotenv::dotenv;
+use tokio::runtime;
 
 use db::models::Reference;
-use db::{establish_connection, SwordDrill, SwordDrillable, VerseFormat};
+use db::{build_pool, connect, prefetch_abbreviations, run_migrations, SwordDrill};
+
+use crate::bookmarks::Bookmarks;
+use crate::commands::CommandResult;
+use crate::error::{CliError, EXIT_CODES};
+use crate::export::Export;
+use crate::format::Output;
 
 #[derive(Parser, Debug)]
 #[clap(
+    name = "bible",
     version = "0.1",
     author = "Dustin Speckhals <dustin1114@gmail.com>",
-    about = "CLI for looking up Bible verses"
+    about = "Toolbox for reading, searching and serving the Bible",
+    after_help = EXIT_CODES
 )]
 struct Opts {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Path of the SQLite database [default: bible.db in the working or
+    /// the user's data directory]
+    #[clap(long, global = true, env = "DATABASE_URL", value_name = "PATH")]
+    database: Option<String>,
+
+    /// Passage to read when no command is given
     #[clap(default_value = "John 3:16")]
     reference: Reference,
+
+    #[clap(flatten)]
+    output: Output,
 }
 
-fn main() -> io::Result<()> {
-    let opts: Opts = Opts::parse();
-    let reference = opts.reference;
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Read a passage (e.g. "John 3:16-18" or "Psalms 23")
+    Read {
+        #[clap(default_value = "John 3:16")]
+        reference: Reference,
 
-    dotenv().ok();
-    let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        #[clap(flatten)]
+        output: Output,
+    },
+    /// Search the text of every verse
+    Search {
+        /// Words to find, or a "quoted phrase"
+        query: String,
+    },
+    /// List the books with their number of chapters
+    Books,
+    /// List the chapters of a book
+    Book {
+        /// Name or abbreviation of the book
+        name: String,
+    },
+    /// Read a random verse
+    Random {
+        #[clap(flatten)]
+        output: Output,
+    },
+    /// Read in a full-screen terminal reader
+    Tui {
+        /// Passage to open
+        #[clap(default_value = "Genesis 1")]
+        reference: Reference,
+
+        /// File of the bookmarks [default: in the user's data directory]
+        #[clap(long, value_name = "FILE")]
+        bookmarks: Option<PathBuf>,
+    },
+    /// Read and search at an interactive prompt
+    Repl {
+        #[clap(flatten)]
+        output: Output,
+    },
+    /// Export a passage, book or the whole Bible to a file
+    Export {
+        #[clap(subcommand)]
+        format: Export,
+    },
+    /// Start the web server
+    Serve(Box<web::Args>),
+}
+
+/// Parses the arguments, rejecting those of reading a passage when
+/// another command is given, and output options the format has no use
+/// for.
+///
+/// `--database` goes with any command, wherever it is given.
+fn parse() -> Opts {
+    let mut cmd = Opts::command();
+    let matches = cmd.get_matches_mut();
+    if matches.subcommand().is_some() {
+        let ignored = matches.ids().find(|id| {
+            id.as_str() != "database"
+                && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+        });
+        if let Some(arg) = ignored.and_then(|id| cmd.get_arguments().find(|a| a.get_id() == id)) {
+            let message = format!("'{}' can't be used with a command", arg);
+            cmd.error(ErrorKind::ArgumentConflict, message).exit();
+        }
+    }
+    let opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let output = match opts.command {
+        Some(Command::Read { ref output, .. })
+        | Some(Command::Random { ref output })
+        | Some(Command::Repl { ref output }) => output,
+        _ => &opts.output,
+    };
+    if let Some(option) = output.unsupported() {
+        let format = output
+            .format
+            .to_possible_value()
+            .expect("Formats have names");
+        let message = format!(
+            "'{}' can't be used with '--format {}'",
+            option,
+            format.get_name()
+        );
+        cmd.error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    opts
+}
+
+/// Gets the database to open: the one given, or else the first default
+/// one that exists.
+///
+/// A database file that was given must exist, rather than an empty one
+/// being created in its place. In-memory databases and `file:` URIs are
+/// left to SQLite.
+fn database(given: Option<String>) -> Result<String, CliError> {
+    if let Some(url) = given {
+        let path = Path::new(&url);
+        if url != ":memory:" && !url.starts_with("file:") && !path.is_file() {
+            return Err(CliError::DatabaseMissing {
+                searched: vec![path.to_owned()],
+            });
+        }
+        return Ok(url);
+    }
+    let searched = paths::default_databases();
+    searched
+        .iter()
+        .find(|p| p.is_file())
+        .map(|p| p.display().to_string())
+        .ok_or(CliError::DatabaseMissing { searched })
+}
 
-    let mut conn = establish_connection(&url);
+/// Runs a command looking the Bible up in the database.
+async fn run(command: Command, database: Option<String>) -> CommandResult {
+    let url = self::database(database)?;
 
-    let result = SwordDrill::verses(&reference, VerseFormat::PlainText, &mut conn);
+    // Build the database from the embedded migrations if needed
+    run_migrations(&mut connect(&url)?)?;
+    let drill = SwordDrill::new(build_pool(&url, 1)?);
+
+    let out = &mut io::stdout().lock();
+    match command {
+        Command::Read { reference, output } => {
+            commands::read(&drill, &reference, &output, out).await
+        }
+        Command::Search { query } => commands::search(&drill, &query, out).await,
+        Command::Books => commands::books(&drill, out).await,
+        Command::Book { name } => commands::book(&drill, &name, out).await,
+        Command::Random { output } => commands::random(&drill, &output, out).await,
+        Command::Tui {
+            reference,
+            bookmarks,
+        } => {
+            let path = bookmarks.unwrap_or_else(Bookmarks::default_path);
+            tui::run(&drill, &reference, Bookmarks::load(&path)?).await
+        }
+        Command::Repl { output } => {
+            let abbreviations = prefetch_abbreviations(&mut connect(&url)?)?;
+            repl::run(&drill, &abbreviations, &output, out).await
+        }
+        Command::Export { format } => export::run(&drill, format, out).await,
+        Command::Serve(_) => unreachable!("The web server runs on its own runtime"),
+    }
+}
+
+fn main() {
+    dotenv().ok();
+    let opts = parse();
+
+    let command = opts.command.unwrap_or(Command::Read {
+        reference: opts.reference,
+        output: opts.output,
+    });
+    let result = match command {
+        Command::Serve(args) => {
+            let args = match opts.database {
+                Some(url) => args.or_database_url(url),
+                None => *args,
+            };
+            web::run(args).map_err(CliError::from)
+        }
+        command => runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(CliError::from)
+            .and_then(|rt| rt.block_on(run(command, opts.database))),
+    };
 
     match result {
-        Ok((book, verses)) => {
-            io::stdout().write_fmt(format_args!(
-                "{}\n",
-                Reference {
-                    book: book.name,
-                    chapter: reference.chapter,
-                    verses: reference.verses
-                }
-            ))?;
-            for v in verses {
-                io::stdout().write_fmt(format_args!("{}\t{}\n", v.verse, v.words))?;
+        Ok(()) => {}
+        // The output was piped into something that stopped reading it
+        Err(CliError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            e.report();
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+mod bookmarks;
+mod commands;
+mod error;
+mod export;
+mod format;
+mod paths;
+mod repl;
+mod tui;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_references() {
+        let long = "Ésaïe ".repeat(20);
+        for reference in ["", long.as_str()] {
+            for args in [vec!["bible", reference], vec!["bible", "read", reference]] {
+                let e = Opts::try_parse_from(args).unwrap_err();
+                assert_eq!(e.kind(), ErrorKind::ValueValidation);
+                // Clap exits with the usage code for errors it prints to stderr
+                assert!(e.use_stderr());
             }
-            Ok(())
         }
-        Err(e) => io::stderr().write_fmt(format_args!("{:?}", e)),
+    }
+    #[test]
+    fn given_database() {
+        let missing = "./tests/missing.db";
+        assert!(matches!(
+            database(Some(missing.to_string())),
+            Err(CliError::DatabaseMissing { searched }) if searched == [PathBuf::from(missing)]
+        ));
+        assert_eq!(database(Some(":memory:".to_string())).unwrap(), ":memory:");
+        assert_eq!(
+            database(Some("Cargo.toml".to_string())).unwrap(),
+            "Cargo.toml"
+        );
     }
 }
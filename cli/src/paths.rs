@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+/// Gets the path of a file in the user's data directory (e.g.
+/// `~/.local/share/biblers` on Linux).
+pub fn data_file(name: &str) -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_default()
+        .join("biblers")
+        .join(name)
+}
+
+/// Databases opened when none is given, in order of preference: `bible.db`
+/// in the working directory, then in the user's data directory.
+pub fn default_databases() -> Vec<PathBuf> {
+    vec![PathBuf::from("bible.db"), data_file("bible.db")]
+}
@@ -0,0 +1,296 @@
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use db::models::{Book, BookAbbreviation, Reference};
+use db::SwordDrillable;
+
+use crate::commands::{self, CommandResult};
+use crate::error::CliError;
+use crate::format::Output;
+use crate::paths;
+
+/// Commands besides references and searches, shown by `help`.
+const HELP: &str = "\
+<reference>    read a passage (e.g. John 3:16-18 or ps 23)
+:16 or :16-18  read verses of the current chapter
+5              read a chapter of the current book
+next, prev     read the next or previous chapter
+/<query>       search the text of every verse
+help, quit";
+
+/// Line typed at the prompt.
+#[derive(Debug, PartialEq)]
+enum Line {
+    Read(Reference),
+    Verses(RangeInclusive<i32>),
+    Chapter(i32),
+    Next,
+    Previous,
+    Search(String),
+    Help,
+    Quit,
+    Empty,
+}
+
+/// Parses a line typed at the prompt.
+fn parse(line: &str) -> Result<Line, CliError> {
+    let line = line.trim();
+    let number = |s: &str| {
+        s.trim()
+            .parse::<i32>()
+            .map_err(|_| CliError::Other(format!("'{}' is not a verse number.", s.trim())))
+    };
+
+    Ok(match line {
+        "" => Line::Empty,
+        "next" | "n" => Line::Next,
+        "prev" | "previous" | "p" => Line::Previous,
+        "help" | "?" => Line::Help,
+        "quit" | "exit" | "q" => Line::Quit,
+        _ => {
+            if let Some(query) = line.strip_prefix('/') {
+                Line::Search(query.trim().to_string())
+            } else if let Some(verses) = line.strip_prefix(':') {
+                match verses.split_once('-') {
+                    Some((from, to)) => Line::Verses(number(from)?..=number(to)?),
+                    None => number(verses).map(|v| Line::Verses(v..=v))?,
+                }
+            } else if let Ok(chapter) = line.parse() {
+                Line::Chapter(chapter)
+            } else {
+                Line::Read(line.parse::<Reference>()?)
+            }
+        }
+    })
+}
+
+/// Completes the book names and abbreviations at the start of a line.
+struct BookCompleter {
+    names: Vec<String>,
+}
+
+impl BookCompleter {
+    /// Creates a completer of the books' names, then of their other
+    /// abbreviations.
+    fn new(books: &[Book], abbreviations: &[BookAbbreviation]) -> Self {
+        let mut names: Vec<String> = books.iter().map(|b| b.name.clone()).collect();
+        let mut others: Vec<String> = abbreviations
+            .iter()
+            .map(|a| a.abbreviation.clone())
+            .filter(|a| !names.iter().any(|n| n.eq_ignore_ascii_case(a)))
+            .collect();
+        others.sort();
+        others.dedup();
+        names.extend(others);
+
+        Self { names }
+    }
+
+    /// Names starting with what was typed, ignoring case.
+    fn candidates(&self, typed: &str) -> Vec<String> {
+        let typed = typed.to_lowercase();
+        self.names
+            .iter()
+            .filter(|n| n.to_lowercase().starts_with(&typed))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Completer for BookCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let typed = &line[..pos];
+        // Only the book of a reference is completed
+        if typed.is_empty() || typed.starts_with(['/', ':']) || typed.contains(':') {
+            return Ok((pos, Vec::new()));
+        }
+        Ok((0, self.candidates(typed)))
+    }
+}
+
+impl Hinter for BookCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for BookCompleter {}
+
+impl Validator for BookCompleter {}
+
+impl Helper for BookCompleter {}
+
+/// Interactive prompt reading passages relative to the last one.
+struct Repl<'a> {
+    drill: &'a dyn SwordDrillable,
+    books: Vec<Book>,
+    output: &'a Output,
+    /// Chapter of the last passage read.
+    current: Option<(Book, i32)>,
+}
+
+impl Repl<'_> {
+    /// Runs a line typed at the prompt. Returns whether to keep going.
+    async fn run(&mut self, line: Line, out: &mut impl Write) -> Result<bool, CliError> {
+        let current = self.current.as_ref();
+        let first = || CliError::Other("Read a passage first.".into());
+        let relative = |chapter, verses| {
+            current
+                .map(|(book, _)| Reference {
+                    book: book.name.clone(),
+                    chapter,
+                    verses,
+                })
+                .ok_or_else(first)
+        };
+
+        let reference = match line {
+            Line::Read(reference) => reference,
+            Line::Verses(verses) => relative(current.map_or(1, |c| c.1), Some(verses))?,
+            Line::Chapter(chapter) => relative(chapter, None)?,
+            Line::Next | Line::Previous => {
+                let (book, chapter) = current.ok_or_else(first)?;
+                let (previous, next) = book.adjacent_chapters(*chapter, &self.books);
+                let (book, chapter) = if line == Line::Next { next } else { previous }
+                    .ok_or_else(|| CliError::Other("There is no chapter there.".into()))?;
+                Reference {
+                    book: book.name.clone(),
+                    chapter,
+                    verses: None,
+                }
+            }
+            Line::Search(query) => {
+                commands::search(self.drill, &query, out).await?;
+                return Ok(true);
+            }
+            Line::Help => {
+                writeln!(out, "{}", HELP)?;
+                return Ok(true);
+            }
+            Line::Quit => return Ok(false),
+            Line::Empty => return Ok(true),
+        };
+
+        let passage = commands::passage(self.drill, &reference, self.output.verse_format()).await?;
+        self.output.write(&passage, out)?;
+        self.current = Some((passage.book, reference.chapter));
+        Ok(true)
+    }
+}
+
+/// Runs the prompt until it is quit, keeping the history of what was
+/// typed in the user's data directory.
+pub async fn run(
+    drill: &dyn SwordDrillable,
+    abbreviations: &[BookAbbreviation],
+    output: &Output,
+    out: &mut impl Write,
+) -> CommandResult {
+    let books = drill.all_books().await?;
+    let mut editor = Editor::<BookCompleter>::new()?;
+    editor.set_helper(Some(BookCompleter::new(&books, abbreviations)));
+    let history = paths::data_file("history");
+    // There's no history the first time
+    let _ = editor.load_history(&history);
+
+    let mut repl = Repl {
+        drill,
+        books,
+        output,
+        current: None,
+    };
+    loop {
+        let prompt = match repl.current {
+            Some((ref book, chapter)) => format!("{} {}> ", book.name, chapter),
+            None => "bible> ".to_string(),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        editor.add_history_entry(line.as_str());
+
+        match parse(&line) {
+            Ok(line) => match repl.run(line, out).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => e.report(),
+            },
+            Err(e) => e.report(),
+        }
+        out.flush()?;
+    }
+
+    if let Some(dir) = history.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    editor.save_history(&history)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use db::models::Testament;
+
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let parse = |line| parse(line).ok();
+        assert_eq!(parse(" :16 "), Some(Line::Verses(16..=16)));
+        assert_eq!(parse(":16-18"), Some(Line::Verses(16..=18)));
+        assert_eq!(parse(":x"), None);
+        assert_eq!(parse("5"), Some(Line::Chapter(5)));
+        assert_eq!(parse("next"), Some(Line::Next));
+        assert_eq!(
+            parse("/living water"),
+            Some(Line::Search("living water".into()))
+        );
+        assert_eq!(
+            parse("jn 3:16"),
+            Some(Line::Read("jn 3:16".parse().unwrap()))
+        );
+        assert_eq!(parse(""), Some(Line::Empty));
+        assert_eq!(parse(&"Ésaïe 40 ".repeat(20)), None);
+    }
+
+    #[test]
+    fn completion() {
+        let book = |id, name: &str| Book {
+            id,
+            name: name.to_string(),
+            chapter_count: 1,
+            testament: Testament::New,
+        };
+        let abbreviation = |id, abbreviation: &str| BookAbbreviation {
+            id,
+            book_id: 1,
+            abbreviation: abbreviation.to_string(),
+        };
+        let completer = BookCompleter::new(
+            &[book(1, "John"), book(2, "1 John"), book(3, "Jude")],
+            &[
+                abbreviation(1, "john"),
+                abbreviation(2, "jn"),
+                abbreviation(3, "jhn"),
+            ],
+        );
+
+        assert_eq!(completer.candidates("j"), ["John", "Jude", "jhn", "jn"]);
+        assert_eq!(completer.candidates("1 j"), ["1 John"]);
+        assert_eq!(completer.candidates("JO"), ["John"]);
+    }
+}
@@ -0,0 +1,457 @@
+use std::io::{self, Stdout};
+use std::panic;
+use std::sync::Arc;
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use tui::{Frame, Terminal};
+
+use db::models::{Book, Reference, Verse};
+use db::{DbError, SwordDrillable, VerseFormat};
+
+use crate::bookmarks::Bookmarks;
+use crate::commands::{self, plain_text, CommandResult};
+
+/// Verses scrolled by a page.
+const PAGE: usize = 10;
+
+/// Shortest query searched as it is typed.
+const MIN_QUERY_LEN: usize = 3;
+
+/// Keys of the reading mode, shown at the bottom of the screen.
+const HELP: &str = "←/→ chapter  ↑/↓ scroll  g go to  / search  b bookmark  B bookmarks  q quit";
+
+/// What the keys currently do.
+enum Mode {
+    Reading,
+    /// Typing a reference to go to.
+    Jump(String),
+    /// Typing a query, with the results of what is typed so far.
+    Search {
+        query: String,
+        results: Vec<(Reference, String)>,
+        list: ListState,
+    },
+    Bookmarks(ListState),
+}
+
+/// Full-screen reader of a chapter at a time.
+struct Reader<'a> {
+    drill: &'a dyn SwordDrillable,
+    books: Vec<Book>,
+    bookmarks: Bookmarks,
+    book: Book,
+    chapter: i32,
+    verses: Vec<Verse>,
+    /// Index of the verse at the top of the screen.
+    top: usize,
+    /// Verse gone to, which is highlighted.
+    selected: Option<i32>,
+    mode: Mode,
+    /// Outcome of the last action (e.g. an error).
+    status: Option<String>,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader opened at a passage.
+    async fn new(
+        drill: &'a dyn SwordDrillable,
+        bookmarks: Bookmarks,
+        start: &Reference,
+    ) -> Result<Reader<'a>, DbError> {
+        let books = drill.all_books().await?;
+        let (book, _) = drill.book(&start.book).await?;
+        let mut reader = Self {
+            drill,
+            books,
+            bookmarks,
+            book,
+            chapter: start.chapter,
+            verses: Vec::new(),
+            top: 0,
+            selected: None,
+            mode: Mode::Reading,
+            status: None,
+        };
+        reader.open(start).await?;
+        Ok(reader)
+    }
+
+    /// The chapter being read, with the selected verse.
+    fn current(&self) -> Reference {
+        Reference {
+            book: self.book.name.clone(),
+            chapter: self.chapter,
+            verses: self.selected.map(|v| v..=v),
+        }
+    }
+
+    /// Opens the chapter of a passage, at its first verse.
+    async fn open(&mut self, reference: &Reference) -> Result<(), DbError> {
+        let chapter = Reference {
+            verses: None,
+            ..reference.clone()
+        };
+        let (book, verses) = self.drill.verses(&chapter, VerseFormat::PlainText).await?;
+        if verses.is_empty() {
+            self.status = Some(format!("{} has no chapter {}", book.name, chapter.chapter));
+            return Ok(());
+        }
+
+        self.selected = reference.verses.as_ref().map(|v| *v.start());
+        self.top = self
+            .selected
+            .and_then(|s| verses.iter().position(|v| v.verse == s))
+            .unwrap_or(0);
+        self.book = book;
+        self.chapter = chapter.chapter;
+        self.verses = verses;
+        self.status = None;
+        Ok(())
+    }
+
+    /// Opens the previous or next chapter, across books.
+    async fn turn(&mut self, forward: bool) -> Result<(), DbError> {
+        let (previous, next) = self.book.adjacent_chapters(self.chapter, &self.books);
+        let chapter = if forward { next } else { previous }.map(|(book, chapter)| Reference {
+            book: book.name.clone(),
+            chapter,
+            verses: None,
+        });
+        match chapter {
+            Some(chapter) => self.open(&chapter).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Handles a key press. Returns whether to keep reading.
+    async fn key(&mut self, key: KeyEvent) -> Result<bool, DbError> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(false);
+        }
+
+        match self.mode {
+            Mode::Reading => return self.reading_key(key).await,
+            Mode::Jump(ref mut input) => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let input = input.trim().to_string();
+                    self.mode = Mode::Reading;
+                    if input.is_empty() {
+                        return Ok(true);
+                    }
+                    match input.parse::<Reference>() {
+                        Ok(reference) => self.open(&reference).await?,
+                        Err(e) => self.status = Some(e.to_string()),
+                    }
+                }
+                KeyCode::Esc => self.mode = Mode::Reading,
+                _ => {}
+            },
+            Mode::Search {
+                ref mut query,
+                ref mut results,
+                ref mut list,
+            } => match key.code {
+                KeyCode::Char(_) | KeyCode::Backspace => {
+                    if let KeyCode::Char(c) = key.code {
+                        query.push(c);
+                    } else {
+                        query.pop();
+                    }
+                    // Search as the query is typed
+                    *results = if query.trim().len() >= MIN_QUERY_LEN {
+                        search(self.drill, query).await?
+                    } else {
+                        Vec::new()
+                    };
+                    list.select((!results.is_empty()).then_some(0));
+                }
+                KeyCode::Up | KeyCode::Down => select(list, results.len(), key.code),
+                KeyCode::Enter => {
+                    let reference = list.selected().and_then(|i| results.get(i)).cloned();
+                    self.mode = Mode::Reading;
+                    if let Some((reference, _)) = reference {
+                        self.open(&reference).await?;
+                    }
+                }
+                KeyCode::Esc => self.mode = Mode::Reading,
+                _ => {}
+            },
+            Mode::Bookmarks(ref mut list) => {
+                let count = self.bookmarks.references.len();
+                match key.code {
+                    KeyCode::Up | KeyCode::Down => select(list, count, key.code),
+                    KeyCode::Enter => {
+                        let reference = list
+                            .selected()
+                            .and_then(|i| self.bookmarks.references.get(i))
+                            .cloned();
+                        self.mode = Mode::Reading;
+                        if let Some(reference) = reference {
+                            self.open(&reference).await?;
+                        }
+                    }
+                    KeyCode::Char('d') | KeyCode::Delete => {
+                        if let Some(i) = list.selected() {
+                            self.status = self.bookmarks.remove(i).err().map(|e| e.to_string());
+                            list.select(Some(i.min(count.saturating_sub(2))));
+                            if count == 1 {
+                                self.mode = Mode::Reading;
+                            }
+                        }
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Reading,
+                    _ => {}
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Handles a key press while reading.
+    async fn reading_key(&mut self, key: KeyEvent) -> Result<bool, DbError> {
+        let last = self.verses.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Down | KeyCode::Char('j') => self.top = (self.top + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => self.top = self.top.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.top = (self.top + PAGE).min(last),
+            KeyCode::PageUp => self.top = self.top.saturating_sub(PAGE),
+            KeyCode::Home => self.top = 0,
+            KeyCode::End => self.top = last,
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('n') => self.turn(true).await?,
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('p') => self.turn(false).await?,
+            KeyCode::Char('g') | KeyCode::Char(':') => self.mode = Mode::Jump(String::new()),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search {
+                    query: String::new(),
+                    results: Vec::new(),
+                    list: ListState::default(),
+                }
+            }
+            KeyCode::Char('b') => {
+                let current = self.current();
+                self.status = Some(match self.bookmarks.toggle(current.clone()) {
+                    Ok(true) => format!("Bookmarked {}", current),
+                    Ok(false) => format!("Removed the bookmark of {}", current),
+                    Err(e) => format!("Could not save the bookmarks: {}", e),
+                });
+            }
+            KeyCode::Char('B') => {
+                if self.bookmarks.references.is_empty() {
+                    self.status = Some("No bookmarks yet".to_string());
+                } else {
+                    let mut list = ListState::default();
+                    list.select(Some(0));
+                    self.mode = Mode::Bookmarks(list);
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Draws the screen.
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(f.size());
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let footer = match self.mode {
+            Mode::Reading => {
+                self.draw_chapter(f, chunks[0]);
+                self.status.clone().unwrap_or_else(|| HELP.to_string())
+            }
+            Mode::Jump(ref input) => {
+                self.draw_chapter(f, chunks[0]);
+                format!("Go to: {}▏", input)
+            }
+            Mode::Search {
+                ref query,
+                ref results,
+                ref mut list,
+            } => {
+                let items: Vec<ListItem> = results
+                    .iter()
+                    .map(|(reference, text)| {
+                        ListItem::new(Spans::from(vec![
+                            Span::styled(format!("{}  ", reference), bold),
+                            Span::raw(text.as_str()),
+                        ]))
+                    })
+                    .collect();
+                let title = format!(" {} results ", results.len());
+                let results = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .highlight_style(highlight);
+                f.render_stateful_widget(results, chunks[0], list);
+                format!("Search: {}▏", query)
+            }
+            Mode::Bookmarks(ref mut list) => {
+                let items: Vec<ListItem> = self
+                    .bookmarks
+                    .references
+                    .iter()
+                    .map(|r| ListItem::new(r.to_string()))
+                    .collect();
+                let bookmarks = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(" Bookmarks "))
+                    .highlight_style(highlight);
+                f.render_stateful_widget(bookmarks, chunks[0], list);
+                "Enter open  d delete  Esc back".to_string()
+            }
+        };
+        f.render_widget(Paragraph::new(footer), chunks[1]);
+    }
+
+    /// Draws the chapter from the verse at the top of the screen.
+    fn draw_chapter<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let lines: Vec<Spans> = self
+            .verses
+            .iter()
+            .skip(self.top)
+            .flat_map(|v| {
+                let style = if Some(v.verse) == self.selected {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                [
+                    Spans::from(vec![
+                        Span::styled(format!("{} ", v.verse), bold),
+                        Span::styled(v.words.as_str(), style),
+                    ]),
+                    Spans::default(),
+                ]
+            })
+            .collect();
+
+        let mut title = format!(" {} {} ", self.book.name, self.chapter);
+        if self.bookmarks.contains(&self.current()) {
+            title.push_str("★ ");
+        }
+        let chapter = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        f.render_widget(chapter, area);
+    }
+}
+
+/// Searches the verses, for listing the results.
+async fn search(
+    drill: &dyn SwordDrillable,
+    query: &str,
+) -> Result<Vec<(Reference, String)>, DbError> {
+    Ok(drill
+        .search(query)
+        .await?
+        .into_iter()
+        .map(|(verse, book)| {
+            let reference = Reference {
+                book: book.name,
+                chapter: verse.chapter,
+                verses: Some(verse.verse..=verse.verse),
+            };
+            (reference, plain_text(&verse.words))
+        })
+        .collect())
+}
+
+/// Moves the selection of a list up or down.
+fn select(list: &mut ListState, len: usize, key: KeyCode) {
+    let selected = list.selected().unwrap_or(0);
+    let selected = match key {
+        KeyCode::Up => selected.saturating_sub(1),
+        _ => (selected + 1).min(len.saturating_sub(1)),
+    };
+    list.select((len > 0).then_some(selected));
+}
+
+/// Leaves raw mode and the alternate screen, and shows the cursor.
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// Restores the terminal when the reader exits, even on errors and
+/// panics, which are reported once the terminal is usable again.
+struct TerminalGuard {
+    /// Puts back the panic hook the reader's wraps.
+    reinstall_hook: Option<Box<dyn FnOnce()>>,
+}
+
+impl TerminalGuard {
+    fn new() -> Self {
+        let previous = Arc::new(panic::take_hook());
+        let hook = previous.clone();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            hook(info);
+        }));
+
+        Self {
+            reinstall_hook: Some(Box::new(move || {
+                let _ = panic::take_hook();
+                panic::set_hook(Box::new(move |info| previous(info)));
+            })),
+        }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+        if let Some(reinstall_hook) = self.reinstall_hook.take() {
+            reinstall_hook();
+        }
+    }
+}
+
+/// Runs the full-screen reader, opened at a passage.
+pub async fn run(
+    drill: &dyn SwordDrillable,
+    start: &Reference,
+    bookmarks: Bookmarks,
+) -> CommandResult {
+    // Fail before taking over the terminal when the passage doesn't exist
+    commands::passage(drill, start, VerseFormat::PlainText).await?;
+    let mut reader = Reader::new(drill, bookmarks, start).await?;
+
+    let _guard = TerminalGuard::new();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal: Terminal<CrosstermBackend<Stdout>> =
+        Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal.hide_cursor()?;
+
+    loop {
+        terminal.draw(|f| reader.draw(f))?;
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => match reader.key(key).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                // Show errors (e.g. an unknown book) without leaving
+                Err(e) => reader.status = Some(e.to_string()),
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+/// Builds a self-contained Bible database at the path given as the only
+/// argument (e.g. `cargo run -p db --bin build-db -- ./bible.db`).
+fn main() {
+    let path = match env::args_os().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("Usage: build-db <PATH>");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = db::build_database(&path) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    println!("Built {}", path.display());
+}
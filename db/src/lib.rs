@@ -3,14 +3,15 @@
 #[macro_use]
 extern crate diesel;
 
+use std::fs;
 use std::path::Path;
 
 use diesel::prelude::*;
 use diesel::r2d2;
-use diesel_migrations::{FileBasedMigrations, MigrationHarness};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use thiserror::Error;
 
-use crate::models::Book;
+use crate::models::{Book, BookAbbreviation};
 
 /// Type of a pooled SQLite connection manager.
 pub type SqliteConnectionManager = r2d2::ConnectionManager<SqliteConnection>;
@@ -20,7 +21,11 @@ pub type SqliteConnectionPool = r2d2::Pool<SqliteConnectionManager>;
 
 pub type DbConnection = SqliteConnection;
 
+/// Migrations that build the Bible database, embedded at compile time.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
 /// Result formats for verses.
+#[derive(Clone, Copy, Debug)]
 pub enum VerseFormat {
     /// Literal HTML.
     Html,
@@ -36,6 +41,9 @@ pub enum DbError {
     #[error("There was a connection pool error.")]
     ConnectionPool { cause: String },
 
+    #[error("Could not connect to the database {}. Root cause: {:?}.", url, cause)]
+    Connection { url: String, cause: String },
+
     #[error("There was a database error. Root cause: {:?}.", cause)]
     Other { cause: String },
 
@@ -46,34 +54,97 @@ pub enum DbError {
     InvalidReference { reference: String },
 }
 
-/// Builds a SQLite connection bool with the given URL.
-pub fn build_pool(db_url: &str) -> SqliteConnectionPool {
+/// Builds a SQLite connection pool with the given URL and max number of
+/// connections.
+pub fn build_pool(db_url: &str, max_size: u32) -> Result<SqliteConnectionPool, DbError> {
     r2d2::Pool::builder()
-        .max_size(15)
+        .max_size(max_size)
         .build(SqliteConnectionManager::new(db_url))
-        .unwrap()
+        .map_err(|e| DbError::ConnectionPool {
+            cause: e.to_string(),
+        })
 }
 
 /// Establishes a non-pooled SQLite connection.
+pub fn connect(db_url: &str) -> Result<SqliteConnection, DbError> {
+    SqliteConnection::establish(db_url).map_err(|e| DbError::Connection {
+        url: db_url.to_owned(),
+        cause: e.to_string(),
+    })
+}
+
+/// Establishes a non-pooled SQLite connection, panicking when it can't.
 pub fn establish_connection(db_url: &str) -> SqliteConnection {
-    SqliteConnection::establish(db_url).unwrap_or_else(|_| panic!("Error connecting to {}", db_url))
+    connect(db_url).unwrap_or_else(|e| panic!("{}", e))
 }
 
 /// Run any pending Diesel migrations.
+///
+/// The migrations are embedded in the binary, so this works from any
+/// working directory.
 pub fn run_migrations(conn: &mut SqliteConnection) -> Result<(), DbError> {
-    let dir = Path::new("./db/migrations");
-    let source = FileBasedMigrations::find_migrations_directory_in_path(dir).map_err(|e| {
-        DbError::Migration {
-            cause: e.to_string(),
-        }
-    })?;
-    conn.run_pending_migrations(source)
+    conn.run_pending_migrations(MIGRATIONS)
         .map(|_| ())
         .map_err(|e| DbError::Migration {
             cause: e.to_string(),
         })
 }
 
+/// Builds a complete, self-contained Bible database at the given path.
+///
+/// The database is built in a temporary file next to `path`, and only
+/// moved into place once every migration has run, so a failed build
+/// never leaves a partial database behind. Fails if `path` already
+/// exists.
+pub fn build_database(path: &Path) -> Result<(), DbError> {
+    let to_db_error = |e: &dyn std::fmt::Display| DbError::Other {
+        cause: format!(
+            "Could not build the database at {}. Cause: {e}",
+            path.display()
+        ),
+    };
+
+    if path.exists() {
+        return Err(to_db_error(&"The file already exists."));
+    }
+    let partial = path.with_extension("partial");
+    if partial.exists() {
+        fs::remove_file(&partial).map_err(|e| to_db_error(&e))?;
+    }
+
+    {
+        let url = partial
+            .to_str()
+            .ok_or_else(|| to_db_error(&"The path is not valid UTF-8."))?;
+        let mut conn = SqliteConnection::establish(url).map_err(|e| to_db_error(&e))?;
+        run_migrations(&mut conn)?;
+        // Compact the file and leave no journal behind
+        diesel::sql_query("VACUUM")
+            .execute(&mut conn)
+            .map_err(|e| to_db_error(&e))?;
+    }
+
+    fs::rename(&partial, path).map_err(|e| to_db_error(&e))
+}
+
+/// Gets a string identifying the version of the database content.
+///
+/// The Bible text only ever changes through migrations, so the list of
+/// applied migrations identifies the content.
+pub fn content_version(conn: &mut SqliteConnection) -> Result<String, DbError> {
+    conn.applied_migrations()
+        .map(|versions| {
+            versions
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .map_err(|e| DbError::Migration {
+            cause: e.to_string(),
+        })
+}
+
 pub fn prefetch_books(conn: &mut SqliteConnection) -> Result<Vec<Book>, DbError> {
     use crate::schema::books;
 
@@ -82,8 +153,45 @@ pub fn prefetch_books(conn: &mut SqliteConnection) -> Result<Vec<Book>, DbError>
     })
 }
 
+/// Gets the abbreviations the books can be looked up by.
+pub fn prefetch_abbreviations(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<BookAbbreviation>, DbError> {
+    use crate::schema::book_abbreviations;
+
+    book_abbreviations::table
+        .load(conn)
+        .map_err(|e| DbError::Other {
+            cause: format!("Could not preload book abbreviations from database. Cause: {e}"),
+        })
+}
+
+mod memory;
 pub mod models;
 mod schema;
 mod sword_drill;
 
-pub use sword_drill::{SwordDrill, SwordDrillable};
+pub use memory::MemorySwordDrill;
+pub use sword_drill::{PoolStats, SwordDrill, SwordDrillable};
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn build() {
+        let path = env::temp_dir().join(format!("biblers-build-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        build_database(&path).unwrap();
+        let mut conn = establish_connection(path.to_str().unwrap());
+        assert!(!conn.has_pending_migration(MIGRATIONS).unwrap());
+
+        // An existing database is never overwritten
+        assert!(build_database(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
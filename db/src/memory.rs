@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use async_trait::async_trait;
+use diesel::prelude::*;
+use tokio::sync::mpsc;
+
+use crate::models::*;
+use crate::sword_drill::{PoolStats, SwordDrill, SwordDrillable};
+use crate::{DbError, SqliteConnectionPool, VerseFormat};
+
+/// Verse text for one format, stored as a single string with the span
+/// of each verse.
+struct Text {
+    spans: Vec<Range<u32>>,
+    words: String,
+}
+
+impl Text {
+    fn new(verses: &[Verse]) -> Self {
+        let mut words = String::with_capacity(verses.iter().map(|v| v.words.len()).sum());
+        let spans = verses
+            .iter()
+            .map(|v| {
+                let start = words.len() as u32;
+                words.push_str(&v.words);
+                start..words.len() as u32
+            })
+            .collect();
+
+        Self { spans, words }
+    }
+
+    fn get(&self, index: usize) -> &str {
+        let span = &self.spans[index];
+        &self.words[span.start as usize..span.end as usize]
+    }
+}
+
+/// Compact, read-only copy of the Bible text.
+///
+/// Verses are kept in canonical order, with an index of where each
+/// chapter starts, so a passage is a contiguous slice.
+pub(crate) struct MemoryStore {
+    abbreviations: HashMap<String, usize>,
+    books: Vec<Book>,
+    /// Verse index range of each chapter, by book index and chapter index.
+    chapters: Vec<Vec<Range<usize>>>,
+    html: Text,
+    ids: Vec<i32>,
+    numbers: Vec<i32>,
+    plain_text: Text,
+}
+
+impl MemoryStore {
+    /// Builds a store from the rows of the Bible tables.
+    ///
+    /// Both verse lists must be ordered by book, chapter and verse, and
+    /// contain the same verses.
+    pub(crate) fn new(
+        mut books: Vec<Book>,
+        abbreviations: Vec<BookAbbreviation>,
+        plain_text: Vec<Verse>,
+        html: Vec<Verse>,
+    ) -> Result<Self, DbError> {
+        let mismatch = plain_text.len() != html.len()
+            || plain_text
+                .iter()
+                .zip(&html)
+                .any(|(p, h)| (p.book, p.chapter, p.verse) != (h.book, h.chapter, h.verse));
+        if mismatch {
+            return Err(DbError::Other {
+                cause: "Plain text and HTML verses do not match.".to_string(),
+            });
+        }
+
+        books.sort_by_key(|b| b.id);
+        let book_index: HashMap<i32, usize> =
+            books.iter().enumerate().map(|(i, b)| (b.id, i)).collect();
+
+        let mut chapters: Vec<Vec<Range<usize>>> = books
+            .iter()
+            .map(|b| vec![0..0; b.chapter_count as usize])
+            .collect();
+        for (i, v) in plain_text.iter().enumerate() {
+            let range = book_index
+                .get(&v.book)
+                .zip(usize::try_from(v.chapter - 1).ok())
+                .and_then(|(b, c)| chapters[*b].get_mut(c))
+                .ok_or_else(|| DbError::Other {
+                    cause: format!("Verse {} is outside of the known books.", v.id),
+                })?;
+            if range.start == range.end {
+                *range = i..i + 1;
+            } else {
+                range.end = i + 1;
+            }
+        }
+
+        let abbreviations = abbreviations
+            .into_iter()
+            .filter_map(|a| {
+                book_index
+                    .get(&a.book_id)
+                    .map(|b| (a.abbreviation.to_lowercase(), *b))
+            })
+            .collect();
+
+        Ok(Self {
+            abbreviations,
+            books,
+            chapters,
+            html: Text::new(&html),
+            ids: plain_text.iter().map(|v| v.id).collect(),
+            numbers: plain_text.iter().map(|v| v.verse).collect(),
+            plain_text: Text::new(&plain_text),
+        })
+    }
+
+    /// Loads the Bible text from the database.
+    fn load(conn: &mut SqliteConnection) -> Result<Self, DbError> {
+        use crate::schema::book_abbreviations as ba;
+        use crate::schema::books as b;
+        use crate::schema::verses as plain_text;
+        use crate::schema::verses_html as html;
+
+        let to_db_error = |e: diesel::result::Error| DbError::Other {
+            cause: format!("Could not load the Bible into memory. Cause: {e}"),
+        };
+
+        Self::new(
+            b::table.order_by(b::id).load(conn).map_err(to_db_error)?,
+            ba::table.load(conn).map_err(to_db_error)?,
+            plain_text::table
+                .order_by((plain_text::book, plain_text::chapter, plain_text::verse))
+                .load(conn)
+                .map_err(to_db_error)?,
+            html::table
+                .order_by((html::book, html::chapter, html::verse))
+                .load(conn)
+                .map_err(to_db_error)?,
+        )
+    }
+
+    /// Finds a book and its index by name or abbreviation.
+    fn find_book(&self, book_name: &str) -> Result<(usize, &Book), DbError> {
+        self.abbreviations
+            .get(&book_name.to_lowercase())
+            .map(|i| (*i, &self.books[*i]))
+            .ok_or_else(|| DbError::BookNotFound {
+                book: book_name.to_owned(),
+            })
+    }
+
+    pub(crate) fn verses(
+        &self,
+        reference: &Reference,
+        format: VerseFormat,
+    ) -> Result<(Book, Vec<Verse>), DbError> {
+        let (index, book) = self.find_book(&reference.book)?;
+        let text = match format {
+            VerseFormat::Html => &self.html,
+            VerseFormat::PlainText => &self.plain_text,
+        };
+
+        let range = usize::try_from(reference.chapter - 1)
+            .ok()
+            .and_then(|c| self.chapters[index].get(c))
+            .cloned()
+            .unwrap_or(0..0);
+        let verses = range
+            .filter(|i| match reference.verses {
+                Some(ref verses) => verses.contains(&self.numbers[*i]),
+                None => true,
+            })
+            .map(|i| Verse {
+                id: self.ids[i],
+                book: book.id,
+                chapter: reference.chapter,
+                verse: self.numbers[i],
+                words: text.get(i).to_string(),
+            })
+            .collect();
+
+        Ok((book.to_owned(), verses))
+    }
+
+    pub(crate) fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+        let (_, book) = self.find_book(book_name)?;
+        Ok((book.to_owned(), (1..=book.chapter_count).collect()))
+    }
+
+    pub(crate) fn all_books(&self) -> Vec<Book> {
+        self.books.to_owned()
+    }
+}
+
+/// Implementation of the [SwordDrillable](crate::sword_drill::SwordDrillable)
+/// trait that serves the Bible from memory.
+///
+/// The Bible is loaded once with [load](MemorySwordDrill::load), after
+/// which verses and books are looked up without querying SQLite. Full
+/// text search still uses SQLite's FTS5 extension through
+/// [SwordDrill](crate::sword_drill::SwordDrill), and so do the formatted
+/// words and the verses looked up by id, which are only needed for
+/// exports and random verses.
+pub struct MemorySwordDrill {
+    search: SwordDrill,
+    store: MemoryStore,
+}
+
+impl MemorySwordDrill {
+    /// Loads the Bible into memory from the database, keeping the pool
+    /// for searches.
+    pub fn load(pool: SqliteConnectionPool) -> Result<Self, DbError> {
+        let store = {
+            let mut conn = pool.get().map_err(|e| DbError::ConnectionPool {
+                cause: e.to_string(),
+            })?;
+            MemoryStore::load(&mut conn)?
+        };
+
+        Ok(Self {
+            search: SwordDrill::new(pool),
+            store,
+        })
+    }
+}
+
+#[async_trait]
+impl SwordDrillable for MemorySwordDrill {
+    async fn verses(
+        &self,
+        reference: &Reference,
+        format: VerseFormat,
+    ) -> Result<(Book, Vec<Verse>), DbError> {
+        self.store.verses(reference, format)
+    }
+
+    async fn words(&self, reference: &Reference) -> Result<(Book, Vec<Word>), DbError> {
+        self.search.words(reference).await
+    }
+
+    async fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+        self.store.book(book_name)
+    }
+
+    async fn all_books(&self) -> Result<Vec<Book>, DbError> {
+        Ok(self.store.all_books())
+    }
+
+    async fn verse_ids(&self) -> Result<Vec<i32>, DbError> {
+        Ok(self.store.ids.to_owned())
+    }
+
+    async fn verse(&self, id: i32) -> Result<(Book, Verse), DbError> {
+        self.search.verse(id).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<(VerseFTS, Book)>, DbError> {
+        self.search.search(query).await
+    }
+
+    async fn ready(&self) -> Result<(), DbError> {
+        self.search.ready().await
+    }
+
+    fn stream_verses(&self) -> mpsc::Receiver<Result<Verse, DbError>> {
+        self.search.stream_verses()
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.search.pool_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> MemoryStore {
+        let book = |id: i32, name: &str, chapter_count: i32| Book {
+            id,
+            name: name.to_string(),
+            chapter_count,
+            testament: Testament::Old,
+        };
+        let abbreviation = |id: i32, book_id: i32, abbreviation: &str| BookAbbreviation {
+            id,
+            book_id,
+            abbreviation: abbreviation.to_string(),
+        };
+        let verse = |id: i32, book: i32, chapter: i32, verse: i32, words: &str| Verse {
+            id,
+            book,
+            chapter,
+            verse,
+            words: words.to_string(),
+        };
+        let verses = |html: bool| {
+            let i = |w: &str| {
+                if html {
+                    format!("<i>{}</i>", w)
+                } else {
+                    w.to_string()
+                }
+            };
+            vec![
+                verse(1, 1, 1, 1, &i("In the beginning")),
+                verse(2, 1, 1, 2, &i("And the earth")),
+                verse(3, 1, 2, 1, &i("Thus the heavens")),
+                verse(4, 2, 1, 1, &i("Now these are the names")),
+            ]
+        };
+
+        MemoryStore::new(
+            vec![book(2, "Exodus", 1), book(1, "Genesis", 2)],
+            vec![
+                abbreviation(1, 1, "genesis"),
+                abbreviation(2, 1, "gen"),
+                abbreviation(3, 2, "exodus"),
+            ],
+            verses(false),
+            verses(true),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn verses() {
+        let store = store();
+
+        let (book, verses) = store
+            .verses(&"Gen 1".parse().unwrap(), VerseFormat::PlainText)
+            .unwrap();
+        assert_eq!(book.name, "Genesis");
+        assert_eq!(verses.len(), 2);
+        assert_eq!(verses[1].words, "And the earth");
+
+        let (_, verses) = store
+            .verses(&"genesis 1:2".parse().unwrap(), VerseFormat::Html)
+            .unwrap();
+        assert_eq!(verses.len(), 1);
+        assert_eq!(verses[0].id, 2);
+        assert_eq!(verses[0].words, "<i>And the earth</i>");
+
+        let (_, verses) = store
+            .verses(&"Exodus 1:1".parse().unwrap(), VerseFormat::PlainText)
+            .unwrap();
+        assert_eq!(verses[0].words, "Now these are the names");
+
+        // Chapter out of range
+        let (_, verses) = store
+            .verses(&"Exodus 2".parse().unwrap(), VerseFormat::PlainText)
+            .unwrap();
+        assert!(verses.is_empty());
+
+        // Unknown book
+        assert!(matches!(
+            store.verses(&"Foo 1".parse().unwrap(), VerseFormat::PlainText),
+            Err(DbError::BookNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn books() {
+        let store = store();
+
+        let (book, chapters) = store.book("GEN").unwrap();
+        assert_eq!(book.name, "Genesis");
+        assert_eq!(chapters, vec![1, 2]);
+
+        let books = store.all_books();
+        assert_eq!(books[0].name, "Genesis");
+        assert_eq!(books[1].name, "Exodus");
+    }
+}
@@ -14,6 +14,26 @@ pub struct Verse {
     pub words: String,
 }
 
+/// Model representing a word of a Bible verse, with its formatting.
+#[derive(Clone, Debug, Deserialize, Queryable, Serialize)]
+pub struct Word {
+    pub id: i32,
+    pub book: i32,
+    pub chapter: i32,
+    pub paragraph: i32,
+    pub verse: i32,
+    /// Position of the word in its verse.
+    pub position: i32,
+    pub word: String,
+    /// Punctuation following the word.
+    pub punctuation: Option<String>,
+    /// Whether the word was supplied by the translators, and is printed
+    /// in italics.
+    pub italic: bool,
+    pub close_parentheses: bool,
+    pub open_parentheses: bool,
+}
+
 /// Enum for the testaments in the Bible (Old or New). This is mapped
 /// to a column in the database table `books`.
 #[derive(Clone, Debug, Deserialize, Serialize, FromSqlRow)]
@@ -43,6 +63,40 @@ pub struct Book {
     pub testament: Testament,
 }
 
+/// Chapter of a book.
+pub type BookChapter<'a> = (&'a Book, i32);
+
+impl Book {
+    /// Gets the chapters before and after one of the book's chapters, in
+    /// the books of the whole Bible.
+    ///
+    /// Past the edges of the book are the last chapter of the previous book
+    /// and the first chapter of the next; there is nothing before Genesis 1
+    /// or after Revelation 22.
+    pub fn adjacent_chapters<'a>(
+        &'a self,
+        chapter: i32,
+        books: &'a [Book],
+    ) -> (Option<BookChapter<'a>>, Option<BookChapter<'a>>) {
+        let index = self.id as usize - 1;
+        let previous = if chapter > 1 {
+            Some((self, chapter - 1))
+        } else {
+            index
+                .checked_sub(1)
+                .and_then(|i| books.get(i))
+                .map(|b| (b, b.chapter_count))
+        };
+        let next = if chapter < self.chapter_count {
+            Some((self, chapter + 1))
+        } else {
+            books.get(index + 1).map(|b| (b, 1))
+        };
+
+        (previous, next)
+    }
+}
+
 /// Model representing a Bible book's abbreviation.
 #[derive(Clone, Debug, Deserialize, Queryable)]
 pub struct BookAbbreviation {
@@ -57,6 +111,7 @@ pub struct VerseFTS {
     pub book: i32,
     pub chapter: i32,
     pub verse: i32,
+    /// Escaped HTML of the verse, with the matched words in `<em>` tags.
     pub words: String,
     pub rank: f32,
 }
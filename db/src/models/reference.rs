@@ -59,9 +59,9 @@ impl FromStr for Reference {
     type Err = DbError;
 
     fn from_str(s: &str) -> Result<Reference, Self::Err> {
-        if s.len() > MAX_REFERENCE_SIZE || s.is_empty() {
+        if s.trim().is_empty() || s.len() > MAX_REFERENCE_SIZE {
             return Err(DbError::InvalidReference {
-                reference: s[..MAX_REFERENCE_SIZE].to_string(),
+                reference: truncate(s, MAX_REFERENCE_SIZE).to_string(),
             });
         }
 
@@ -131,6 +131,17 @@ impl FromStr for Reference {
     }
 }
 
+/// Cuts a string to at most `max` bytes, without splitting a character.
+fn truncate(s: &str, max: usize) -> &str {
+    let end = s
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|end| *end <= max)
+        .last()
+        .unwrap_or(0);
+    &s[..end]
+}
+
 /// Parse a [Match](String.html) into an i32.
 fn parse_num(s: String) -> Result<i32, DbError> {
     s.parse().map_err(|_| DbError::InvalidReference {
@@ -141,10 +152,11 @@ fn parse_num(s: String) -> Result<i32, DbError> {
 #[cfg(test)]
 mod tests {
     use crate::models::Reference;
+    use crate::DbError;
 
     #[test]
     fn fmt() {
-        vec![
+        [
             ("Genesis 50", "Genesis", 50, None),
             ("Joel 2:", "Joel", 2, None),
             ("Song of Solomon 1", "Song of Solomon", 1, None),
@@ -171,7 +183,7 @@ mod tests {
 
     #[test]
     fn from_str() {
-        vec![
+        [
             ("Genesis 50", "Genesis", 50, None),
             ("Song of Solomon 1", "Song of Solomon", 1, None),
             ("3 John 1", "3 John", 1, None),
@@ -195,4 +207,22 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn invalid() {
+        let invalid = |raw: &str| match raw.parse::<Reference>() {
+            Err(DbError::InvalidReference { reference }) => reference,
+            r => panic!("{:?} parsed as {:?}", raw, r),
+        };
+
+        assert_eq!(invalid(""), "");
+        assert_eq!(invalid("   "), "   ");
+        assert_eq!(invalid("Genesis"), "Genesis");
+        // Cut before the character straddling the limit
+        assert_eq!(invalid(&"é".repeat(60)), "é".repeat(50));
+        assert_eq!(
+            invalid(&format!("a{}", "é".repeat(60))),
+            format!("a{}", "é".repeat(49))
+        );
+    }
 }
@@ -1,26 +1,55 @@
+use async_trait::async_trait;
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel::sql_types::{Integer, Text};
+use tokio::sync::mpsc;
+use tokio::task;
 
 use crate::models::*;
-use crate::{DbError, VerseFormat};
+use crate::{DbError, SqliteConnectionPool, VerseFormat};
 
 /// Max number of search results returned from the database.
 const SEARCH_RESULT_LIMIT: i64 = 15;
 
+/// Number of verses a stream reads with a pooled connection at a time,
+/// and reads ahead of its receiver. The connection is returned between
+/// batches, so a slow receiver never holds on to one.
+const VERSE_STREAM_BATCH: i64 = 1024;
+
+/// Markers around matched words in FTS5 highlights, which are replaced
+/// with `<em>` tags once the verse text is escaped.
+const HIGHLIGHT_START: char = '\u{1}';
+const HIGHLIGHT_END: char = '\u{2}';
+
 sql_function!(
     fn highlight(table_name: Text, column_index: Integer, prefix: Text, suffix: Text) -> Text
 );
 
+/// Statistics of a connection pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of open connections.
+    pub connections: u32,
+    /// Number of open connections not checked out.
+    pub idle_connections: u32,
+    /// Max number of connections.
+    pub max_size: u32,
+}
+
 /// Trait implemented by types that can query for and return types of Bible structures.
-pub trait SwordDrillable {
+#[async_trait]
+pub trait SwordDrillable: Send + Sync {
     /// Looks up Bible verses for the given reference.
-    fn verses(
+    async fn verses(
+        &self,
         reference: &Reference,
         format: VerseFormat,
-        conn: &mut SqliteConnection,
     ) -> Result<(Book, Vec<Verse>), DbError>;
 
+    /// Looks up the words of the Bible verses for the given reference,
+    /// with their formatting, in order.
+    async fn words(&self, reference: &Reference) -> Result<(Book, Vec<Word>), DbError>;
+
     /// Looks up the Bible book with the given book name.
     ///
     /// The inputted name argument can be either the cannonical book name
@@ -29,240 +58,530 @@ pub trait SwordDrillable {
     /// manner.
     ///
     /// If found, returns the resulting book and the list of its chapters.
-    fn book(book_name: &str, conn: &mut SqliteConnection) -> Result<(Book, Vec<i32>), DbError>;
+    async fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError>;
 
     /// Gets all books in the Bible.
-    fn all_books(conn: &mut SqliteConnection) -> Result<Vec<Book>, DbError>;
+    async fn all_books(&self) -> Result<Vec<Book>, DbError>;
+
+    /// Gets the ids of every verse of the Bible, in canonical order.
+    async fn verse_ids(&self) -> Result<Vec<i32>, DbError>;
+
+    /// Looks up a verse as plain text by id, with its book.
+    async fn verse(&self, id: i32) -> Result<(Book, Verse), DbError>;
 
     /// Searches the database using the SQLite 3 full text search extension.
     ///
     /// The inputted query string can be of two different formats:
     ///
     /// - `test foo`: match each word as its own token, and use that
-    /// to search.
+    ///   to search.
     /// - `"test foo"`: match the entire phrase. For the King James
-    /// version of the Bible, this is safe because there are no literal
-    /// quotation marks. This cannot be assumed safe in other translations.
+    ///   version of the Bible, this is safe because there are no literal
+    ///   quotation marks. This cannot be assumed safe in other translations.
     ///
     /// All characters other than alpha and quotations are stripped out.
-    fn search(query: &str, conn: &mut SqliteConnection) -> Result<Vec<(VerseFTS, Book)>, DbError>;
+    async fn search(&self, query: &str) -> Result<Vec<(VerseFTS, Book)>, DbError>;
+
+    /// Checks that the Bible can be queried.
+    async fn ready(&self) -> Result<(), DbError>;
+
+    /// Streams every verse of the Bible as plain text, in canonical order.
+    ///
+    /// Verses are read as the receiver takes them, so they are never all
+    /// in memory, and reading stops when the receiver is dropped. Waiting
+    /// on the receiver holds neither a connection nor a blocking thread.
+    fn stream_verses(&self) -> mpsc::Receiver<Result<Verse, DbError>>;
+
+    /// Gets the statistics of the connection pool, if there is one.
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
 }
 
 /// Main implementation for the [SwordDrillable](crate::sword_drill::SwordDrillable) trait.
-pub struct SwordDrill;
+///
+/// Queries run against a SQLite connection pool on Tokio's blocking
+/// thread pool, so they never block the async executor.
+#[derive(Clone)]
+pub struct SwordDrill {
+    pool: SqliteConnectionPool,
+}
+
+impl SwordDrill {
+    /// Creates a new sword drill that queries the given pool.
+    pub fn new(pool: SqliteConnectionPool) -> Self {
+        Self { pool }
+    }
+
+    /// Runs a query with a pooled connection on a blocking thread.
+    async fn run<T, F>(&self, query: F) -> Result<T, DbError>
+    where
+        F: FnOnce(&mut SqliteConnection) -> Result<T, DbError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get().map_err(|e| DbError::ConnectionPool {
+                cause: e.to_string(),
+            })?;
+            query(&mut conn)
+        })
+        .await
+        .map_err(|e| DbError::Other {
+            cause: e.to_string(),
+        })?
+    }
+}
 
+#[async_trait]
 impl SwordDrillable for SwordDrill {
-    fn verses(
+    async fn verses(
+        &self,
         reference: &Reference,
         format: VerseFormat,
-        conn: &mut SqliteConnection,
     ) -> Result<(Book, Vec<Verse>), DbError> {
-        use crate::schema::verses as plain_text;
-        use crate::schema::verses_html as html;
+        let reference = reference.to_owned();
+        self.run(move |conn| verses(conn, &reference, format)).await
+    }
+
+    async fn words(&self, reference: &Reference) -> Result<(Book, Vec<Word>), DbError> {
+        let reference = reference.to_owned();
+        self.run(move |conn| words(conn, &reference)).await
+    }
+
+    async fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+        let book_name = book_name.to_owned();
+        self.run(move |conn| book(conn, &book_name)).await
+    }
+
+    async fn all_books(&self) -> Result<Vec<Book>, DbError> {
+        self.run(all_books).await
+    }
+
+    async fn verse_ids(&self) -> Result<Vec<i32>, DbError> {
+        self.run(verse_ids).await
+    }
+
+    async fn verse(&self, id: i32) -> Result<(Book, Verse), DbError> {
+        self.run(move |conn| verse(conn, id)).await
+    }
 
-        let (book, _) = Self::book(&reference.book.to_lowercase(), conn)?;
+    async fn search(&self, query: &str) -> Result<Vec<(VerseFTS, Book)>, DbError> {
+        let query = query.to_owned();
+        self.run(move |conn| search(conn, &query)).await
+    }
 
-        match format {
-            VerseFormat::PlainText => {
-                let mut query = plain_text::table
-                    .filter(plain_text::book.eq(book.id))
-                    .filter(plain_text::chapter.eq(reference.chapter))
-                    .order_by((plain_text::chapter.asc(), plain_text::verse.asc()))
-                    .into_boxed();
+    async fn ready(&self) -> Result<(), DbError> {
+        self.run(ready).await
+    }
 
-                if let Some(ref verses) = reference.verses {
-                    query = query.filter(plain_text::verse.between(verses.start(), verses.end()));
+    fn stream_verses(&self) -> mpsc::Receiver<Result<Verse, DbError>> {
+        let (tx, rx) = mpsc::channel(VERSE_STREAM_BATCH as usize);
+        let drill = self.clone();
+        task::spawn(async move {
+            let mut after = None;
+            loop {
+                let batch = match drill.run(move |conn| verse_batch(conn, after)).await {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                let last = batch.last().map(|v| (v.book, v.chapter, v.verse));
+                let done = batch.len() < VERSE_STREAM_BATCH as usize;
+                for verse in batch {
+                    // Nobody is listening anymore
+                    if tx.send(Ok(verse)).await.is_err() {
+                        return;
+                    }
                 }
-                query.load(conn)
-            }
-            VerseFormat::Html => {
-                let mut query = html::table
-                    .filter(html::book.eq(book.id))
-                    .filter(html::chapter.eq(reference.chapter))
-                    .order_by((html::chapter.asc(), html::verse.asc()))
-                    .into_boxed();
-
-                if let Some(ref verses) = reference.verses {
-                    query = query.filter(html::verse.between(verses.start(), verses.end()));
+                match last {
+                    Some(last) if !done => after = Some(last),
+                    _ => return,
                 }
-                query.load(conn)
             }
+        });
+        rx
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        let state = self.pool.state();
+        Some(PoolStats {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: self.pool.max_size(),
+        })
+    }
+}
+
+fn verses(
+    conn: &mut SqliteConnection,
+    reference: &Reference,
+    format: VerseFormat,
+) -> Result<(Book, Vec<Verse>), DbError> {
+    use crate::schema::verses as plain_text;
+    use crate::schema::verses_html as html;
+
+    let (book, _) = book(conn, &reference.book.to_lowercase())?;
+
+    match format {
+        VerseFormat::PlainText => {
+            let mut query = plain_text::table
+                .filter(plain_text::book.eq(book.id))
+                .filter(plain_text::chapter.eq(reference.chapter))
+                .order_by((plain_text::chapter.asc(), plain_text::verse.asc()))
+                .into_boxed();
+
+            if let Some(ref verses) = reference.verses {
+                query = query.filter(plain_text::verse.between(verses.start(), verses.end()));
+            }
+            query.load(conn)
+        }
+        VerseFormat::Html => {
+            let mut query = html::table
+                .filter(html::book.eq(book.id))
+                .filter(html::chapter.eq(reference.chapter))
+                .order_by((html::chapter.asc(), html::verse.asc()))
+                .into_boxed();
+
+            if let Some(ref verses) = reference.verses {
+                query = query.filter(html::verse.between(verses.start(), verses.end()));
+            }
+            query.load(conn)
         }
-        .map(|verses| (book, verses))
+    }
+    .map(|verses| (book, verses))
+    .map_err(|e| DbError::Other {
+        cause: e.to_string(),
+    })
+}
+
+fn words(conn: &mut SqliteConnection, reference: &Reference) -> Result<(Book, Vec<Word>), DbError> {
+    use crate::schema::words as w;
+
+    let (book, _) = book(conn, &reference.book.to_lowercase())?;
+
+    let mut query = w::table
+        .filter(w::book.eq(book.id))
+        .filter(w::chapter.eq(reference.chapter))
+        .order_by((w::verse.asc(), w::position.asc()))
+        .into_boxed();
+    if let Some(ref verses) = reference.verses {
+        query = query.filter(w::verse.between(verses.start(), verses.end()));
+    }
+
+    query
+        .load(conn)
+        .map(|words| (book, words))
         .map_err(|e| DbError::Other {
             cause: e.to_string(),
         })
-    }
+}
 
-    fn book(book_name: &str, conn: &mut SqliteConnection) -> Result<(Book, Vec<i32>), DbError> {
-        use crate::schema::book_abbreviations as ba;
-        use crate::schema::books as b;
-
-        let (book, _): (Book, BookAbbreviation) = b::table
-            .inner_join(ba::table)
-            .filter(ba::abbreviation.eq(book_name.to_lowercase()))
-            .first::<(Book, BookAbbreviation)>(conn)
-            .map_err(|e| match e {
-                Error::NotFound => DbError::BookNotFound {
-                    book: book_name.to_owned(),
-                },
-                e => DbError::Other {
-                    cause: e.to_string(),
-                },
-            })?;
-        let chapters: Vec<i32> = (1..=book.chapter_count).collect();
+fn book(conn: &mut SqliteConnection, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+    use crate::schema::book_abbreviations as ba;
+    use crate::schema::books as b;
+
+    let (book, _): (Book, BookAbbreviation) = b::table
+        .inner_join(ba::table)
+        .filter(ba::abbreviation.eq(book_name.to_lowercase()))
+        .first::<(Book, BookAbbreviation)>(conn)
+        .map_err(|e| match e {
+            Error::NotFound => DbError::BookNotFound {
+                book: book_name.to_owned(),
+            },
+            e => DbError::Other {
+                cause: e.to_string(),
+            },
+        })?;
+    let chapters: Vec<i32> = (1..=book.chapter_count).collect();
 
-        Ok((book, chapters))
-    }
+    Ok((book, chapters))
+}
+
+fn all_books(conn: &mut SqliteConnection) -> Result<Vec<Book>, DbError> {
+    use crate::schema::books::dsl::*;
 
-    fn all_books(conn: &mut SqliteConnection) -> Result<Vec<Book>, DbError> {
-        use crate::schema::books::dsl::*;
+    books.order_by(id).load(conn).map_err(|e| DbError::Other {
+        cause: e.to_string(),
+    })
+}
+
+fn verse_ids(conn: &mut SqliteConnection) -> Result<Vec<i32>, DbError> {
+    use crate::schema::verses::dsl::*;
 
-        books.order_by(id).load(conn).map_err(|e| DbError::Other {
+    verses
+        .select(id)
+        .order_by((book.asc(), chapter.asc(), verse.asc()))
+        .load(conn)
+        .map_err(|e| DbError::Other {
             cause: e.to_string(),
         })
+}
+
+fn verse(conn: &mut SqliteConnection, verse_id: i32) -> Result<(Book, Verse), DbError> {
+    use crate::schema::books;
+    use crate::schema::verses;
+
+    verses::table
+        .inner_join(books::table)
+        .filter(verses::id.eq(verse_id))
+        .first::<(Verse, Book)>(conn)
+        .map(|(verse, book)| (book, verse))
+        .map_err(|e| DbError::Other {
+            cause: match e {
+                Error::NotFound => format!("Verse {} was not found.", verse_id),
+                e => e.to_string(),
+            },
+        })
+}
+
+/// Reads the next verses of a stream, in canonical order after the book,
+/// chapter and verse of the last one read.
+fn verse_batch(
+    conn: &mut SqliteConnection,
+    after: Option<(i32, i32, i32)>,
+) -> Result<Vec<Verse>, DbError> {
+    use crate::schema::verses::dsl::*;
+
+    let mut query = verses
+        .order_by((book.asc(), chapter.asc(), verse.asc()))
+        .limit(VERSE_STREAM_BATCH)
+        .into_boxed();
+    if let Some((b, c, v)) = after {
+        query = query.filter(
+            book.gt(b)
+                .or(book.eq(b).and(chapter.gt(c)))
+                .or(book.eq(b).and(chapter.eq(c)).and(verse.gt(v))),
+        );
     }
+    query.load(conn).map_err(|e| DbError::Other {
+        cause: e.to_string(),
+    })
+}
+
+fn ready(conn: &mut SqliteConnection) -> Result<(), DbError> {
+    use crate::schema::books::dsl::*;
+
+    books
+        .select(id)
+        .first::<i32>(conn)
+        .map(|_| ())
+        .map_err(|e| DbError::Other {
+            cause: e.to_string(),
+        })
+}
 
-    fn search(query: &str, conn: &mut SqliteConnection) -> Result<Vec<(VerseFTS, Book)>, DbError> {
-        use crate::schema::books;
-        use crate::schema::verses_fts;
+fn search(conn: &mut SqliteConnection, query: &str) -> Result<Vec<(VerseFTS, Book)>, DbError> {
+    use crate::schema::books;
+    use crate::schema::verses_fts;
 
-        let had_quote = query.contains('"');
+    let had_quote = query.contains('"');
 
-        // Replace all characters that aren't alpha or space
-        let query: String = query
-            .chars()
-            .filter(|c| c.is_alphabetic() || *c == ' ')
-            .collect();
+    // Replace all characters that aren't alpha or space
+    let query: String = query
+        .chars()
+        .filter(|c| c.is_alphabetic() || *c == ' ')
+        .collect();
 
-        // Don't even try to run the query if there are no characters
-        if query.trim().is_empty() {
-            return Ok(vec![]);
-        }
+    // Don't even try to run the query if there are no characters
+    if query.trim().is_empty() {
+        return Ok(vec![]);
+    }
 
-        // Add back quotes safely if it had a quote before, and was removed
-        // This makes FTS5 query the string as a phrase.
-        let query = if had_quote {
-            format!("\"{}\"", query)
-        } else {
-            query
-        };
-
-        verses_fts::table
-            .inner_join(books::table.on(books::id.eq(verses_fts::book)))
-            .select((
-                (
-                    verses_fts::book,
-                    verses_fts::chapter,
-                    verses_fts::verse,
-                    highlight(verses_fts::text, 3, "<em>", "</em>"),
-                    verses_fts::rank,
+    // Add back quotes safely if it had a quote before, and was removed
+    // This makes FTS5 query the string as a phrase.
+    let query = if had_quote {
+        format!("\"{}\"", query)
+    } else {
+        query
+    };
+
+    verses_fts::table
+        .inner_join(books::table.on(books::id.eq(verses_fts::book)))
+        .select((
+            (
+                verses_fts::book,
+                verses_fts::chapter,
+                verses_fts::verse,
+                highlight(
+                    verses_fts::text,
+                    3,
+                    HIGHLIGHT_START.to_string(),
+                    HIGHLIGHT_END.to_string(),
                 ),
-                (
-                    books::id,
-                    books::name,
-                    books::chapter_count,
-                    books::testament,
-                ),
-            ))
-            .filter(verses_fts::text.eq(format!("{}*", query)))
-            .order_by(verses_fts::rank)
-            .limit(SEARCH_RESULT_LIMIT)
-            .load::<(VerseFTS, Book)>(conn)
-            .map_err(|e| DbError::Other {
-                cause: e.to_string(),
-            })
+                verses_fts::rank,
+            ),
+            (
+                books::id,
+                books::name,
+                books::chapter_count,
+                books::testament,
+            ),
+        ))
+        .filter(verses_fts::text.eq(format!("{}*", query)))
+        .order_by(verses_fts::rank)
+        .limit(SEARCH_RESULT_LIMIT)
+        .load::<(VerseFTS, Book)>(conn)
+        .map(|results| {
+            results
+                .into_iter()
+                .map(|(verse, book)| {
+                    let words = highlighted(&verse.words);
+                    (VerseFTS { words, ..verse }, book)
+                })
+                .collect()
+        })
+        .map_err(|e| DbError::Other {
+            cause: e.to_string(),
+        })
+}
+
+/// Turns a highlight with markers into HTML whose only tags are balanced
+/// `<em>` tags around the matched words.
+///
+/// Everything else in the verse is escaped, so highlights can be output
+/// as is in pages and by scripts.
+fn highlighted(raw: &str) -> String {
+    let mut html = String::with_capacity(raw.len() + 16);
+    let mut open = false;
+    for c in raw.chars() {
+        match c {
+            HIGHLIGHT_START if !open => {
+                html.push_str("<em>");
+                open = true;
+            }
+            HIGHLIGHT_END if open => {
+                html.push_str("</em>");
+                open = false;
+            }
+            HIGHLIGHT_START | HIGHLIGHT_END => {}
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            c => html.push(c),
+        }
     }
+    if open {
+        html.push_str("</em>");
+    }
+
+    html
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
-
-    use diesel_migrations::{FileBasedMigrations, MigrationHarness};
+    use diesel::r2d2;
 
     use super::*;
-    use crate::establish_connection;
+    use crate::{run_migrations, SqliteConnectionManager};
 
     #[test]
-    fn all() {
-        let mut conn = {
-            let mut conn = establish_connection(":memory:");
-            let source =
-                FileBasedMigrations::find_migrations_directory_in_path(Path::new("./migrations"))
-                    .unwrap();
-            conn.run_pending_migrations(source).unwrap();
-            conn
-        };
-
-        conn.test_transaction::<_, DbError, _>(|c| {
-            // Verses
-            {
-                let result = SwordDrill::verses(
-                &"Psalms 119:105".parse().unwrap(),
-                VerseFormat::PlainText,
-                c,
-            )?;
+    fn highlights() {
+        let raw =
+            "\u{1}Light\u{2} & <b>dark</b> <em onclick=\"x\">\u{1}\u{1}x\u{2}\u{2}</em> \u{1}'";
+        assert_eq!(
+            highlighted(raw),
+            "<em>Light</em> &amp; &lt;b&gt;dark&lt;/b&gt; &lt;em onclick=\"x\"&gt;<em>x</em>&lt;/em&gt; <em>'</em>"
+        );
+
+        // No tag but `<em>` and `</em>` ever makes it through
+        let tags = highlighted(raw).replace("<em>", "").replace("</em>", "");
+        assert!(!tags.contains('<') && !tags.contains('>'));
+    }
+
+    #[tokio::test]
+    async fn all() -> Result<(), DbError> {
+        // A single connection, so every query sees the same in-memory database
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::new(":memory:"))
+            .unwrap();
+        run_migrations(&mut pool.get().unwrap())?;
+        let sd = SwordDrill::new(pool);
+
+        // Verses
+        {
+            let result = sd
+                .verses(&"Psalms 119:105".parse().unwrap(), VerseFormat::PlainText)
+                .await?;
 
             assert_eq!(result.0.name, "Psalms");
             assert_eq!(
                 result.1[0].words,
                 "NUN. Thy word is a lamp unto my feet, and a light unto my path."
             );
-            }
+        }
 
-            // Book
-            {
-                let result = SwordDrill::book("psa", c)?;
+        // Book
+        {
+            let result = sd.book("psa").await?;
 
             assert_eq!(result.0.name, "Psalms");
             assert_eq!(result.1.len(), 150);
-            }
+        }
 
-            // All books
-            {
-                let result = SwordDrill::all_books(c)?;
+        // All books
+        {
+            let result = sd.all_books().await?;
 
             assert_eq!(result.len(), 66);
             assert_eq!(result[64].name, "Jude");
-            }
+        }
 
-            // Search - Fuzzy words
-            {
-                let result = SwordDrill::search("fire hammer rock", c)?;
-
-                assert_eq!(result.len(), 1);
-                assert_eq!(result[0].0.book, 24);
-                assert_eq!(result[0].0.chapter, 23);
-                assert_eq!(result[0].0.verse, 29);
-                assert_eq!(
-                    result[0].0.words,
-                    "Is not my word like as a <em>fire</em>? saith the LORD; and like a <em>hammer</em> that breaketh the <em>rock</em> in pieces?",
-                );
-                assert_eq!(result[0].1.name, "Jeremiah");
-            }
+        // Verses by id
+        {
+            let ids = sd.verse_ids().await?;
+            assert_eq!(ids.len(), 31102);
 
-            // Search - Leading number followed by a space returns an empty result
-            {
-                let result = SwordDrill::search("1 ", c)?;
-                assert_eq!(result.len(), 0);
-            }
+            let (book, verse) = sd.verse(ids[0]).await?;
+            assert_eq!(book.name, "Genesis");
+            assert_eq!((verse.chapter, verse.verse), (1, 1));
+        }
 
-            // Search - Phrase
-            {
-                let result = SwordDrill::search("\"like as a fire\"", c)?;
-
-                assert_eq!(result.len(), 1);
-                assert_eq!(result[0].0.book, 24);
-                assert_eq!(result[0].0.chapter, 23);
-                assert_eq!(result[0].0.verse, 29);
-                assert_eq!(
-                    result[0].0.words,
-                    "Is not my word <em>like as a fire</em>? saith the LORD; and like a hammer that breaketh the rock in pieces?",
-                );
-                assert_eq!(result[0].1.name, "Jeremiah");
-            }
-            Ok(())
-        });
+        // Readiness
+        {
+            sd.ready().await?;
+            assert_eq!(sd.pool_stats().unwrap().max_size, 1);
+        }
+
+        // Search - Fuzzy words
+        {
+            let result = sd.search("fire hammer rock").await?;
+
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].0.book, 24);
+            assert_eq!(result[0].0.chapter, 23);
+            assert_eq!(result[0].0.verse, 29);
+            assert_eq!(
+                result[0].0.words,
+                "Is not my word like as a <em>fire</em>? saith the LORD; and like a <em>hammer</em> that breaketh the <em>rock</em> in pieces?",
+            );
+            assert_eq!(result[0].1.name, "Jeremiah");
+        }
+
+        // Search - Leading number followed by a space returns an empty result
+        {
+            let result = sd.search("1 ").await?;
+            assert_eq!(result.len(), 0);
+        }
+
+        // Search - Phrase
+        {
+            let result = sd.search("\"like as a fire\"").await?;
+
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].0.book, 24);
+            assert_eq!(result[0].0.chapter, 23);
+            assert_eq!(result[0].0.verse, 29);
+            assert_eq!(
+                result[0].0.words,
+                "Is not my word <em>like as a fire</em>? saith the LORD; and like a hammer that breaketh the rock in pieces?",
+            );
+            assert_eq!(result[0].1.name, "Jeremiah");
+        }
+
+        Ok(())
     }
 }
@@ -6,4 +6,41 @@ cd "/home/$USER/bible.rs"
 
 docker-compose pull
 
-sudo systemctl restart biblers.service
+# Replace the app container without downtime: start the new version next
+# to the running one, and stop the old one once the new one is ready. The
+# gateway resolves `app` again every few seconds, so it moves over to the
+# new container, retrying the other one when a connection fails.
+old=$(docker-compose ps -q app)
+if [ -z "$old" ]; then
+    sudo systemctl restart biblers.service
+    exit
+fi
+
+docker-compose up -d --no-deps --no-recreate --scale app=2 app
+new=$(docker-compose ps -q app | grep -v "$old")
+ip=$(docker inspect -f '{{range .NetworkSettings.Networks}}{{.IPAddress}}{{end}}' "$new")
+
+# Checked from the host, which reaches the containers on their network, as
+# the gateway's nginx image has no HTTP client
+ready=false
+for _ in $(seq 60); do
+    if curl -sf --max-time 5 "http://$ip:8080/readyz" > /dev/null; then
+        ready=true
+        break
+    fi
+    sleep 2
+done
+if [ "$ready" != true ]; then
+    echo "New app container isn't ready, keeping the running one" >&2
+    docker rm -f "$new"
+    exit 1
+fi
+
+# Drain the old container: wait out the gateway's resolver TTL (see
+# config/nginx) so it knows of the new container before the old one stops
+sleep 10
+
+# SIGTERM lets the old container finish its requests (see shutdown_timeout)
+docker stop --time 35 "$old"
+docker rm "$old"
+docker-compose up -d --no-deps --no-recreate --scale app=1 app
@@ -1,15 +1,87 @@
 // Search box
 (function () {
+    // Verses searched in the page when there is no search API, as on a
+    // static copy of the site
+    var searchIndex = null;
+    var maxResults = 15;
+    var htmlEscapes = { "&": "&amp;", "<": "&lt;", ">": "&gt;", "\"": "&quot;", "'": "&#39;" };
+
+    function escapeHtml(s) {
+        return String(s).replace(/[&<>"']/g, function (c) {
+            return htmlEscapes[c];
+        });
+    }
+
+    function escapeRegExp(s) {
+        return s.replace(/[.*+?^${}()|[\]\\]/g, "\\$&");
+    }
+
+    // Escapes the text of a verse, only marking up the matches (with
+    // <em>), as the search API does
+    function highlighted(text, highlight) {
+        var html = "";
+        var last = 0;
+        var match;
+        highlight.lastIndex = 0;
+        while ((match = highlight.exec(text))) {
+            html += escapeHtml(text.slice(last, match.index)) + "<em>" + escapeHtml(match[0]) + "</em>";
+            last = highlight.lastIndex;
+        }
+        return html + escapeHtml(text.slice(last));
+    }
+
+    function searchVerses(q) {
+        if (!searchIndex) {
+            searchIndex = fetch("/static/search-index.json").then(function (resp) {
+                return resp.json();
+            });
+        }
+
+        var terms = q.toLowerCase().split(/\W+/).filter(Boolean).map(escapeRegExp);
+        var patterns = terms.map(function (term) {
+            return new RegExp("\\b" + term, "i");
+        });
+        var highlight = new RegExp("\\b(?:" + terms.join("|") + ")\\w*", "gi");
+
+        return searchIndex.then(function (index) {
+            var matches = [];
+            for (var i = 0; i < index.verses.length && matches.length < maxResults; i++) {
+                var verse = index.verses[i];
+                var text = verse[3];
+                if (terms.length && patterns.every(function (p) { return p.test(text); })) {
+                    var book = index.books[verse[0]];
+                    matches.push({
+                        link: {
+                            label: book + " " + verse[1] + ":" + verse[2],
+                            url: "/" + encodeURIComponent(book) + "/" + verse[1] + "#v" + verse[2]
+                        },
+                        text: highlighted(text, highlight)
+                    });
+                }
+            }
+            return { matches: matches };
+        });
+    }
+
     function getResults(q, cb) {
         if (!q) {
             cb([]);
             return;
         }
 
-        fetch("/api/search?q=" + encodeURIComponent(q)).then(function (resp) {
-            return resp.json();
-        }).then(function (data) {
-            cb(data.matches);
+        var results = searchIndex ? searchVerses(q) : fetch(
+            "/api/search?q=" + encodeURIComponent(q)
+        ).then(function (resp) {
+            if (resp.status === 404) {
+                return searchVerses(q);
+            }
+            // Keep the current suggestions when rate limited
+            return resp.ok ? resp.json() : null;
+        });
+        results.then(function (data) {
+            if (data) {
+                cb(data.matches);
+            }
         });
     }
 
@@ -18,7 +90,7 @@
             source: getResults,
             templates: {
                 suggestion: function (result) {
-                    return "<p><i>" + result.link.label + "</i> | " + result.text + "</p>";
+                    return "<p><i>" + escapeHtml(result.link.label) + "</i> | " + result.text + "</p>";
                 }
             }
         }
@@ -100,5 +172,5 @@
 
 // Service worker registration
 if (navigator.serviceWorker) {
-    navigator.serviceWorker.register("/static/js/sw.js", { scope: "/" });
+    navigator.serviceWorker.register("/sw.js", { scope: "/" });
 }
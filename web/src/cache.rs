@@ -0,0 +1,314 @@
+use std::io::Cursor;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use actix_web::web::Bytes;
+use lru::LruCache;
+use tokio::sync::OnceCell;
+
+use db::models::{Book, Reference};
+use db::SwordDrillable;
+
+use crate::controllers::Representation;
+use crate::export::{epub, ExportError, Range};
+use crate::middleware::ContentVersion;
+use crate::responder::{ScriptHashes, Site};
+
+/// Translation of the Bible text served by the application.
+pub const TRANSLATION: &str = "kjv";
+
+/// Response header telling whether a page came from the page cache.
+pub const X_CACHE: &str = "X-Cache";
+
+/// Key of a rendered page in the [page cache](PageCache).
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PageKey {
+    reference: String,
+    representation: Representation,
+    translation: &'static str,
+}
+
+impl PageKey {
+    /// Creates a key for a chapter rendered in a representation.
+    ///
+    /// The chapter is keyed by its canonical book name so that
+    /// differently cased URLs share a page. Books that are not named
+    /// canonically (e.g. abbreviations) and ranges of verses have no key.
+    fn new(reference: &Reference, representation: Representation, books: &[Book]) -> Option<Self> {
+        if reference.verses.is_some() {
+            return None;
+        }
+        let book = books
+            .iter()
+            .find(|b| b.name.eq_ignore_ascii_case(reference.book.trim()))?;
+        let reference = Reference {
+            book: book.name.to_owned(),
+            ..reference.to_owned()
+        };
+
+        Some(Self {
+            reference: reference.to_string(),
+            representation,
+            translation: TRANSLATION,
+        })
+    }
+
+    /// Bytes a page takes in the cache with its key.
+    fn size(&self, page: &Page) -> usize {
+        self.reference.len() + page.body.len()
+    }
+}
+
+/// Rendered page.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub body: Bytes,
+    /// Inline scripts of an HTML page, allowed by its security policy.
+    pub scripts: ScriptHashes,
+}
+
+impl From<Bytes> for Page {
+    fn from(body: Bytes) -> Self {
+        Self {
+            body,
+            scripts: ScriptHashes::default(),
+        }
+    }
+}
+
+/// Pages of the cache, with the bytes they take.
+struct Pages {
+    lru: LruCache<PageKey, Page>,
+    bytes: usize,
+}
+
+/// Least recently used cache of rendered pages, bounded by their size.
+///
+/// The Bible only has about 1,189 chapters, so most traffic can be
+/// answered without touching the database or the template engine. Only
+/// whole chapters rendered for the configured site are cached, so verse
+/// ranges and other hosts can't crowd them out.
+pub struct PageCache {
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    pages: Option<Mutex<Pages>>,
+    site: String,
+}
+
+impl PageCache {
+    /// Creates a cache of the pages of a site holding at most `capacity`
+    /// bytes. A capacity of zero disables caching.
+    pub fn new(capacity: usize, site: &Site) -> Self {
+        Self {
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            pages: (capacity > 0).then(|| {
+                Mutex::new(Pages {
+                    lru: LruCache::unbounded(),
+                    bytes: 0,
+                })
+            }),
+            site: site.url.to_string(),
+        }
+    }
+
+    /// Gets the key of a reference rendered in a representation for a
+    /// site, if the page is one to cache.
+    pub fn key(
+        &self,
+        reference: &Reference,
+        representation: Representation,
+        site: &Site,
+        books: &[Book],
+    ) -> Option<PageKey> {
+        if self.pages.is_none() || site.url.as_str() != self.site {
+            return None;
+        }
+        PageKey::new(reference, representation, books)
+    }
+
+    /// Gets a cached page, counting the hit or miss.
+    pub fn get(&self, key: &PageKey) -> Option<Page> {
+        let page = self
+            .pages
+            .as_ref()
+            .and_then(|p| p.lock().unwrap().lru.get(key).cloned());
+
+        match page {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        page
+    }
+
+    /// Caches a page, dropping the least recently used ones it doesn't
+    /// fit next to. Pages larger than the whole cache aren't cached.
+    pub fn insert(&self, key: PageKey, page: Page) {
+        let size = key.size(&page);
+        let Some(ref pages) = self.pages else {
+            return;
+        };
+        if size > self.capacity {
+            return;
+        }
+
+        let mut pages = pages.lock().unwrap();
+        if let Some(old) = pages.lru.pop(&key) {
+            pages.bytes -= key.size(&old);
+        }
+        while pages.bytes + size > self.capacity {
+            match pages.lru.pop_lru() {
+                Some((k, p)) => pages.bytes -= k.size(&p),
+                None => break,
+            }
+        }
+        pages.bytes += size;
+        pages.lru.put(key, page);
+    }
+
+    /// Number of pages currently cached.
+    pub fn len(&self) -> usize {
+        self.pages
+            .as_ref()
+            .map_or(0, |p| p.lock().unwrap().lru.len())
+    }
+
+    /// Bytes the cached pages take.
+    pub fn bytes(&self) -> usize {
+        self.pages.as_ref().map_or(0, |p| p.lock().unwrap().bytes)
+    }
+
+    /// Number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups that had to render the page.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Number of built EPUBs kept in memory.
+const EPUB_CACHE_SIZE: usize = 16;
+
+/// EPUBs built for the current content version, the least recently
+/// downloaded ones being dropped.
+///
+/// Concurrent downloads of a range wait for the same build, and every
+/// build is stamped with when the content last changed, so the same range
+/// always gets the same bytes.
+pub struct EpubCache {
+    epubs: Mutex<LruCache<String, Arc<OnceCell<Bytes>>>>,
+    modified: SystemTime,
+}
+
+impl EpubCache {
+    pub fn new(version: &ContentVersion) -> Self {
+        Self {
+            epubs: Mutex::new(LruCache::new(NonZeroUsize::new(EPUB_CACHE_SIZE).unwrap())),
+            modified: version.modified(),
+        }
+    }
+
+    /// Gets the EPUB of a range, building it if it isn't cached.
+    pub async fn get(
+        &self,
+        drill: &dyn SwordDrillable,
+        range: &Range,
+    ) -> Result<Bytes, ExportError> {
+        let cell = self
+            .epubs
+            .lock()
+            .unwrap()
+            .get_or_insert(range.title.clone(), Default::default)
+            .clone();
+        cell.get_or_try_init(|| async {
+            let out = epub::write(drill, range, self.modified, Cursor::new(Vec::new())).await?;
+            Ok(Bytes::from(out.into_inner()))
+        })
+        .await
+        .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::BOOKS;
+
+    #[test]
+    fn page_key() {
+        let site = Site::default();
+        let cache = PageCache::new(1024, &site);
+        let key = |r: &str, representation, site: &Site| {
+            cache.key(&r.parse().unwrap(), representation, site, &BOOKS)
+        };
+        let html = |r: &str| key(r, Representation::Html, &site);
+
+        assert_eq!(html("john 3"), html("John 3"));
+        assert_ne!(html("John 3"), key("John 3", Representation::Json, &site));
+        assert!(html("jhn 3").is_none());
+
+        // Verse ranges
+        assert!(html("John 3:16").is_none());
+
+        // Pages rendered for another site
+        let mirror = Site {
+            url: "https://mirror.example".parse().unwrap(),
+            ..Site::default()
+        };
+        assert!(key("John 3", Representation::Html, &mirror).is_none());
+    }
+
+    #[test]
+    fn page_cache() {
+        let site = Site::default();
+        let page = || Page::from(Bytes::from_static(b"<html>"));
+        // Room for one page and its key
+        let cache = PageCache::new(12, &site);
+        let key = |r: &str| {
+            cache
+                .key(&r.parse().unwrap(), Representation::Html, &site, &BOOKS)
+                .unwrap()
+        };
+
+        assert!(cache.get(&key("John 3")).is_none());
+        cache.insert(key("John 3"), page());
+        assert_eq!(cache.get(&key("John 3")).unwrap().body, "<html>");
+        assert_eq!(cache.bytes(), 12);
+
+        // Replacing a page doesn't count it twice
+        cache.insert(key("John 3"), page());
+        assert_eq!(cache.bytes(), 12);
+
+        // Least recently used page is evicted
+        cache.insert(key("John 4"), page());
+        assert!(cache.get(&key("John 3")).is_none());
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.bytes(), 12);
+
+        // Larger than the cache
+        cache.insert(key("John 5"), Bytes::from_static(b"<html></html>").into());
+        assert!(cache.get(&key("John 5")).is_none());
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 3);
+
+        // Disabled
+        let cache = PageCache::new(0, &site);
+        assert!(cache
+            .key(
+                &"John 3".parse().unwrap(),
+                Representation::Html,
+                &site,
+                &BOOKS
+            )
+            .is_none());
+    }
+}
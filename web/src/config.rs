@@ -0,0 +1,718 @@
+use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use actix_web::http::header::HeaderName;
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use serde_derive::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::middleware::{Network, Quota, RateLimits};
+use crate::responder::{
+    Creator, Site, DEFAULT_CREATOR_NAME, DEFAULT_CREATOR_URL, DEFAULT_SITE_NAME, DEFAULT_SITE_URL,
+};
+
+/// Address listened on when neither an address nor a socket is configured.
+const DEFAULT_BIND: &str = "0.0.0.0:8080";
+
+/// `Cache-Control` max age in seconds for generated pages (one week).
+const DEFAULT_CACHE_MAX_AGE: u32 = 60 * 60 * 24 * 7;
+
+/// Location of the SQLite database.
+const DEFAULT_DATABASE_URL: &str = "/tmp/biblers.db";
+
+/// Log filter used when `RUST_LOG` isn't set.
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Megabytes of rendered pages kept in the page cache.
+const DEFAULT_PAGE_CACHE_MB: usize = 64;
+
+/// Requests per minute and burst of a client to the reference API.
+const DEFAULT_API_RATE: (u32, u32) = (600, 100);
+
+/// Requests per minute and burst of a client to search, which allows for
+/// the autocomplete querying as the user types.
+const DEFAULT_SEARCH_RATE: (u32, u32) = (60, 20);
+
+/// Max number of pooled SQLite connections.
+const DEFAULT_POOL_SIZE: u32 = 15;
+
+/// Seconds in-flight requests are given to finish when shutting down.
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+/// Directory of the static files.
+const DEFAULT_STATIC_DIR: &str = "./web/dist";
+
+/// Directory of the Handlebars templates.
+pub const DEFAULT_TEMPLATE_DIR: &str = "./web/templates/";
+
+/// TLS termination by the server itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tls {
+    /// PEM file of the certificate chain.
+    pub cert: PathBuf,
+    /// PEM file of the certificate's private key.
+    pub key: PathBuf,
+    /// Address redirecting plain HTTP requests to HTTPS.
+    pub redirect_bind: Option<String>,
+}
+
+/// Error for an invalid server configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Could not read the configuration file '{}': {}", path.display(), cause)]
+    Read { path: PathBuf, cause: io::Error },
+
+    #[error("Could not parse the configuration file '{}': {}", path.display(), cause)]
+    Parse {
+        path: PathBuf,
+        cause: toml::de::Error,
+    },
+
+    #[error("Invalid value for '{}': {}", field, reason)]
+    Invalid { field: &'static str, reason: String },
+}
+
+/// Implementations of [SwordDrillable](db::SwordDrillable) the server
+/// can be run with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Query SQLite for every lookup.
+    Sqlite,
+    /// Serve verses and books from memory, only searching with SQLite.
+    Memory,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqlite" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(format!("Unknown backend '{}'", s)),
+        }
+    }
+}
+
+/// Commands of the web server besides serving the site.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Render every page into a directory, to host as static files
+    Build {
+        /// Directory to write the site to
+        #[clap(long, value_name = "DIR")]
+        out: PathBuf,
+    },
+}
+
+/// Command line flags of the web server.
+///
+/// Every flag can also be set with its environment variable, or in the
+/// TOML configuration file under the flag's name in snake case. Flags
+/// take precedence over the environment, which takes precedence over
+/// the file.
+#[derive(Debug, Parser)]
+#[clap(version, about = "Bible.rs web server")]
+pub struct Args {
+    /// TOML configuration file
+    #[clap(short, long, env = "BIBLERS_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Requests per minute a client can make to the reference API; 0
+    /// disables the limit
+    #[clap(long, env = "BIBLERS_API_RATE_LIMIT")]
+    api_rate_limit: Option<u32>,
+
+    /// Requests a client can make at once to the reference API
+    #[clap(long, env = "BIBLERS_API_RATE_BURST")]
+    api_rate_burst: Option<u32>,
+
+    /// Address to listen on (e.g. 0.0.0.0:8080); can be repeated
+    #[clap(long, env = "BIBLERS_BIND", value_delimiter = ',')]
+    bind: Vec<String>,
+
+    /// Implementation serving the Bible: `sqlite` or `memory`
+    #[clap(long, env = "SWORD_DRILL")]
+    backend: Option<String>,
+
+    /// `Cache-Control` max age of generated pages, in seconds
+    #[clap(long, env = "CACHE_MAX_AGE")]
+    cache_max_age: Option<u32>,
+
+    /// Header with the client's address set by a trusted proxy (e.g.
+    /// X-Real-IP), of which the last address is used; the peer address is
+    /// used otherwise
+    #[clap(long, env = "BIBLERS_CLIENT_IP_HEADER")]
+    client_ip_header: Option<String>,
+
+    /// Name of the site's creator, credited in structured data
+    #[clap(long, env = "BIBLERS_CREATOR_NAME")]
+    creator_name: Option<String>,
+
+    /// URL of the site's creator
+    #[clap(long, env = "BIBLERS_CREATOR_URL")]
+    creator_url: Option<String>,
+
+    /// Path of the SQLite database
+    #[clap(long, env = "DATABASE_URL")]
+    database_url: Option<String>,
+
+    /// Log filter (e.g. `info` or `warn,web=debug`)
+    #[clap(long, env = "RUST_LOG")]
+    log_level: Option<String>,
+
+    /// Address to serve Prometheus metrics on, apart from the site (e.g.
+    /// 127.0.0.1:9090); metrics aren't served without it
+    #[clap(long, env = "BIBLERS_METRICS_BIND")]
+    metrics_bind: Option<String>,
+
+    /// Megabytes of rendered pages kept in memory; 0 disables the cache
+    #[clap(long, env = "PAGE_CACHE_MB")]
+    page_cache_mb: Option<usize>,
+
+    /// References to render at startup: `all` or a comma separated list
+    #[clap(long, env = "PAGE_CACHE_WARM")]
+    page_cache_warm: Option<String>,
+
+    /// Max number of pooled database connections
+    #[clap(long, env = "BIBLERS_POOL_SIZE")]
+    pool_size: Option<u32>,
+
+    /// Client addresses or networks (e.g. 10.0.0.0/8) that are never rate
+    /// limited; can be repeated
+    #[clap(long, env = "BIBLERS_RATE_LIMIT_ALLOW", value_delimiter = ',')]
+    rate_limit_allow: Vec<String>,
+
+    /// Requests per minute a client can make to search; 0 disables the
+    /// limit
+    #[clap(long, env = "BIBLERS_SEARCH_RATE_LIMIT")]
+    search_rate_limit: Option<u32>,
+
+    /// Requests a client can make at once to search
+    #[clap(long, env = "BIBLERS_SEARCH_RATE_BURST")]
+    search_rate_burst: Option<u32>,
+
+    /// Sentry DSN errors are reported to
+    #[clap(long, env = "SENTRY_DSN")]
+    sentry_dsn: Option<String>,
+
+    /// Seconds in-flight requests are given to finish on SIGTERM
+    #[clap(long, env = "BIBLERS_SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: Option<u64>,
+
+    /// Name of the site, used in titles and structured data
+    #[clap(long, env = "BIBLERS_SITE_NAME")]
+    site_name: Option<String>,
+
+    /// Canonical URL of the site (e.g. https://bible.rs)
+    #[clap(long, env = "BIBLERS_SITE_URL")]
+    site_url: Option<String>,
+
+    /// Take the site URL from each request's Forwarded or Host headers
+    #[clap(long, env = "BIBLERS_SITE_URL_FROM_REQUEST")]
+    site_url_from_request: bool,
+
+    /// Directory of the static files
+    #[clap(long, env = "BIBLERS_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+
+    /// Directory of the Handlebars templates
+    #[clap(long, env = "BIBLERS_TEMPLATE_DIR")]
+    template_dir: Option<PathBuf>,
+
+    /// PEM certificate chain; serves HTTPS on the bound addresses when set
+    /// with --tls-key
+    #[clap(long, env = "BIBLERS_TLS_CERT")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the certificate
+    #[clap(long, env = "BIBLERS_TLS_KEY")]
+    tls_key: Option<PathBuf>,
+
+    /// Address to listen on for plain HTTP, redirecting to HTTPS (e.g.
+    /// 0.0.0.0:80)
+    #[clap(long, env = "BIBLERS_TLS_REDIRECT_BIND")]
+    tls_redirect_bind: Option<String>,
+
+    /// Unix domain socket to listen on
+    #[clap(long, env = "BIBLERS_UNIX_SOCKET")]
+    unix_socket: Option<PathBuf>,
+
+    /// Number of worker threads; defaults to the number of CPUs
+    #[clap(long, env = "BIBLERS_WORKERS")]
+    workers: Option<usize>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    /// Uses a database when the arguments don't name one.
+    pub fn or_database_url(mut self, url: String) -> Self {
+        self.database_url.get_or_insert(url);
+        self
+    }
+
+    /// Takes the command to run instead of serving the site, if any.
+    pub(crate) fn take_command(&mut self) -> Option<Command> {
+        self.command.take()
+    }
+}
+
+/// Contents of the TOML configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    api_rate_burst: Option<u32>,
+    api_rate_limit: Option<u32>,
+    bind: Option<Vec<String>>,
+    backend: Option<String>,
+    cache_max_age: Option<u32>,
+    client_ip_header: Option<String>,
+    creator_name: Option<String>,
+    creator_url: Option<String>,
+    database_url: Option<String>,
+    log_level: Option<String>,
+    metrics_bind: Option<String>,
+    page_cache_mb: Option<usize>,
+    page_cache_warm: Option<String>,
+    pool_size: Option<u32>,
+    rate_limit_allow: Option<Vec<String>>,
+    search_rate_burst: Option<u32>,
+    search_rate_limit: Option<u32>,
+    sentry_dsn: Option<String>,
+    shutdown_timeout: Option<u64>,
+    site_name: Option<String>,
+    site_url: Option<String>,
+    site_url_from_request: Option<bool>,
+    static_dir: Option<PathBuf>,
+    template_dir: Option<PathBuf>,
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+    tls_redirect_bind: Option<String>,
+    unix_socket: Option<PathBuf>,
+    workers: Option<usize>,
+}
+
+impl FileConfig {
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|cause| ConfigError::Read {
+            path: path.to_owned(),
+            cause,
+        })?;
+        toml::from_str(&contents).map_err(|cause| ConfigError::Parse {
+            path: path.to_owned(),
+            cause,
+        })
+    }
+}
+
+/// Validated configuration of the web server.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub backend: Backend,
+    pub bind: Vec<String>,
+    pub cache_max_age: u32,
+    pub database_url: String,
+    pub log_level: String,
+    pub metrics_bind: Option<String>,
+    pub page_cache_mb: usize,
+    pub page_cache_warm: Option<String>,
+    pub pool_size: u32,
+    pub rate_limits: RateLimits,
+    pub sentry_dsn: Option<String>,
+    pub shutdown_timeout: u64,
+    pub site: Site,
+    pub static_dir: PathBuf,
+    pub template_dir: PathBuf,
+    pub tls: Option<Tls>,
+    pub unix_socket: Option<PathBuf>,
+    pub workers: Option<usize>,
+}
+
+impl Config {
+    /// Merges parsed arguments with the configuration file they point
+    /// to, and validates the result.
+    pub fn from_args(args: Args) -> Result<Self, ConfigError> {
+        let file = match args.config {
+            Some(ref path) => FileConfig::read(path)?,
+            None => FileConfig::default(),
+        };
+        Self::merge(args, file)
+    }
+
+    fn merge(args: Args, file: FileConfig) -> Result<Self, ConfigError> {
+        let unix_socket = args.unix_socket.or(file.unix_socket);
+        let mut bind = match args.bind {
+            bind if !bind.is_empty() => bind,
+            _ => file.bind.unwrap_or_default(),
+        };
+        if bind.is_empty() && unix_socket.is_none() {
+            bind.push(DEFAULT_BIND.to_string());
+        }
+
+        let tls = match (
+            args.tls_cert.or(file.tls_cert),
+            args.tls_key.or(file.tls_key),
+            args.tls_redirect_bind.or(file.tls_redirect_bind),
+        ) {
+            (Some(cert), Some(key), redirect_bind) => Some(Tls {
+                cert,
+                key,
+                redirect_bind,
+            }),
+            (None, None, None) => None,
+            (cert, _, _) => {
+                return Err(ConfigError::Invalid {
+                    field: if cert.is_some() {
+                        "tls_key"
+                    } else {
+                        "tls_cert"
+                    },
+                    reason: "a certificate and its key are both required for TLS".to_string(),
+                })
+            }
+        };
+
+        let config = Self {
+            backend: args
+                .backend
+                .or(file.backend)
+                .map(|b| b.parse())
+                .transpose()
+                .map_err(|reason| ConfigError::Invalid {
+                    field: "backend",
+                    reason,
+                })?
+                .unwrap_or(Backend::Sqlite),
+            bind,
+            cache_max_age: args
+                .cache_max_age
+                .or(file.cache_max_age)
+                .unwrap_or(DEFAULT_CACHE_MAX_AGE),
+            database_url: args
+                .database_url
+                .or(file.database_url)
+                .unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string()),
+            log_level: args
+                .log_level
+                .or(file.log_level)
+                .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+            metrics_bind: args.metrics_bind.or(file.metrics_bind),
+            page_cache_mb: args
+                .page_cache_mb
+                .or(file.page_cache_mb)
+                .unwrap_or(DEFAULT_PAGE_CACHE_MB),
+            page_cache_warm: args.page_cache_warm.or(file.page_cache_warm),
+            pool_size: args
+                .pool_size
+                .or(file.pool_size)
+                .unwrap_or(DEFAULT_POOL_SIZE),
+            rate_limits: RateLimits {
+                allow: match args.rate_limit_allow {
+                    allow if !allow.is_empty() => allow,
+                    _ => file.rate_limit_allow.unwrap_or_default(),
+                }
+                .iter()
+                .map(|n| n.parse::<Network>())
+                .collect::<Result<_, _>>()
+                .map_err(|reason| ConfigError::Invalid {
+                    field: "rate_limit_allow",
+                    reason,
+                })?,
+                api: quota(
+                    "api_rate_burst",
+                    args.api_rate_limit
+                        .or(file.api_rate_limit)
+                        .unwrap_or(DEFAULT_API_RATE.0),
+                    args.api_rate_burst
+                        .or(file.api_rate_burst)
+                        .unwrap_or(DEFAULT_API_RATE.1),
+                )?,
+                client_ip_header: args.client_ip_header.or(file.client_ip_header),
+                search: quota(
+                    "search_rate_burst",
+                    args.search_rate_limit
+                        .or(file.search_rate_limit)
+                        .unwrap_or(DEFAULT_SEARCH_RATE.0),
+                    args.search_rate_burst
+                        .or(file.search_rate_burst)
+                        .unwrap_or(DEFAULT_SEARCH_RATE.1),
+                )?,
+            },
+            sentry_dsn: args
+                .sentry_dsn
+                .or(file.sentry_dsn)
+                .filter(|dsn| !dsn.is_empty()),
+            shutdown_timeout: args
+                .shutdown_timeout
+                .or(file.shutdown_timeout)
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+            site: Site {
+                creator: Creator {
+                    name: args
+                        .creator_name
+                        .or(file.creator_name)
+                        .unwrap_or_else(|| DEFAULT_CREATOR_NAME.to_string()),
+                    url: args
+                        .creator_url
+                        .or(file.creator_url)
+                        .unwrap_or_else(|| DEFAULT_CREATOR_URL.to_string()),
+                },
+                from_request: args.site_url_from_request
+                    || file.site_url_from_request.unwrap_or_default(),
+                name: args
+                    .site_name
+                    .or(file.site_name)
+                    .unwrap_or_else(|| DEFAULT_SITE_NAME.to_string()),
+                url: parse_site_url(
+                    &args
+                        .site_url
+                        .or(file.site_url)
+                        .unwrap_or_else(|| DEFAULT_SITE_URL.to_string()),
+                )?,
+            },
+            static_dir: args
+                .static_dir
+                .or(file.static_dir)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_STATIC_DIR)),
+            template_dir: args
+                .template_dir
+                .or(file.template_dir)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_TEMPLATE_DIR)),
+            tls,
+            unix_socket,
+            workers: args.workers.or(file.workers),
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the values that can't be checked while parsing.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, reason: String| Err(ConfigError::Invalid { field, reason });
+
+        for addr in &self.bind {
+            if addr.to_socket_addrs().is_err() {
+                return invalid("bind", format!("'{}' is not a socket address", addr));
+            }
+        }
+        if let Some(ref addr) = self.metrics_bind {
+            if addr.to_socket_addrs().is_err() {
+                return invalid(
+                    "metrics_bind",
+                    format!("'{}' is not a socket address", addr),
+                );
+            }
+        }
+        if let Some(addr) = self.tls.as_ref().and_then(|t| t.redirect_bind.as_ref()) {
+            if addr.to_socket_addrs().is_err() {
+                return invalid(
+                    "tls_redirect_bind",
+                    format!("'{}' is not a socket address", addr),
+                );
+            }
+            if self.bind.is_empty() {
+                return invalid(
+                    "tls_redirect_bind",
+                    "requires an address to redirect to".to_string(),
+                );
+            }
+        }
+        if cfg!(not(unix)) && self.unix_socket.is_some() {
+            return invalid(
+                "unix_socket",
+                "is only supported on Unix platforms".to_string(),
+            );
+        }
+        if self.workers == Some(0) {
+            return invalid("workers", "must be at least 1".to_string());
+        }
+        if self.pool_size == 0 {
+            return invalid("pool_size", "must be at least 1".to_string());
+        }
+        for (field, dir) in [
+            ("static_dir", &self.static_dir),
+            ("template_dir", &self.template_dir),
+        ] {
+            if !dir.is_dir() {
+                return invalid(field, format!("'{}' is not a directory", dir.display()));
+            }
+        }
+        if let Some(ref header) = self.rate_limits.client_ip_header {
+            if HeaderName::from_str(header).is_err() {
+                return invalid("client_ip_header", format!("'{}' is not a header", header));
+            }
+        }
+        if let Some(directive) = invalid_log_directive(&self.log_level) {
+            return invalid("log_level", format!("'{}' is not a log filter", directive));
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the quota of a route group, which is disabled without any
+/// requests per minute.
+fn quota(field: &'static str, per_minute: u32, burst: u32) -> Result<Option<Quota>, ConfigError> {
+    match (per_minute, burst) {
+        (0, _) => Ok(None),
+        (_, 0) => Err(ConfigError::Invalid {
+            field,
+            reason: "must be at least 1".to_string(),
+        }),
+        (per_minute, burst) => Ok(Some(Quota { per_minute, burst })),
+    }
+}
+
+/// Parses the canonical site URL, which must be an absolute HTTP(S) URL.
+fn parse_site_url(url: &str) -> Result<Url, ConfigError> {
+    let invalid = |reason: String| ConfigError::Invalid {
+        field: "site_url",
+        reason,
+    };
+
+    let url = Url::parse(url).map_err(|e| invalid(format!("'{}' {}", url, e)))?;
+    match url.scheme() {
+        "http" | "https" if url.has_host() => Ok(url),
+        _ => Err(invalid(format!("'{}' is not an HTTP(S) URL", url))),
+    }
+}
+
+/// Finds the first directive of a log filter (e.g. `warn,web=debug`)
+/// with an invalid level.
+///
+/// A directive without `=` is either a level or a module name, so only
+/// the levels of `module=level` directives can be wrong.
+fn invalid_log_directive(filter: &str) -> Option<&str> {
+    filter
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .find(|d| match d.split_once('=') {
+            Some((_, level)) => LevelFilter::from_str(level.trim()).is_err(),
+            None => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::try_parse_from(["web"].iter().chain(flags)).unwrap()
+    }
+
+    fn dirs() -> [&'static str; 4] {
+        ["--static-dir", "./dist", "--template-dir", "./templates"]
+    }
+
+    #[test]
+    fn merge() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            bind = ["127.0.0.1:8000"]
+            pool_size = 4
+            site_url = "https://example.com"
+            static_dir = "./dist"
+            template_dir = "./templates"
+            "#,
+        )
+        .unwrap();
+
+        // The file fills in what isn't given as a flag
+        let config = Config::merge(args(&["--pool-size", "8"]), file).unwrap();
+        assert_eq!(config.bind, vec!["127.0.0.1:8000"]);
+        assert_eq!(config.pool_size, 8);
+        assert_eq!(config.site.url.as_str(), "https://example.com/");
+        assert_eq!(config.site.name, DEFAULT_SITE_NAME);
+        assert_eq!(config.shutdown_timeout, DEFAULT_SHUTDOWN_TIMEOUT);
+        assert_eq!(
+            config.rate_limits.search,
+            Some(Quota {
+                per_minute: DEFAULT_SEARCH_RATE.0,
+                burst: DEFAULT_SEARCH_RATE.1
+            })
+        );
+
+        // Route groups are limited separately
+        let mut flags = dirs().to_vec();
+        flags.extend(["--api-rate-limit", "0", "--search-rate-burst", "5"]);
+        let config = Config::merge(args(&flags), FileConfig::default()).unwrap();
+        assert_eq!(config.rate_limits.api, None);
+        assert_eq!(config.rate_limits.search.unwrap().burst, 5);
+
+        // A socket replaces the default address
+        let mut flags = dirs().to_vec();
+        flags.extend(["--unix-socket", "/tmp/biblers.sock"]);
+        let config = Config::merge(args(&flags), FileConfig::default()).unwrap();
+        assert!(config.bind.is_empty());
+
+        let flags = dirs();
+        let config = Config::merge(args(&flags), FileConfig::default()).unwrap();
+        assert_eq!(config.bind, vec![DEFAULT_BIND]);
+
+        assert!(toml::from_str::<FileConfig>("port = 80").is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let error = |flags: &[&str]| match Config::merge(args(flags), FileConfig::default()) {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            result => panic!("Expected an invalid field, got {:?}", result),
+        };
+        let with_dirs = |extra: &[&'static str]| [&dirs()[..], extra].concat();
+
+        assert_eq!(error(&with_dirs(&["--bind", "localhost"])), "bind");
+        assert_eq!(
+            error(&with_dirs(&["--metrics-bind", "localhost"])),
+            "metrics_bind"
+        );
+        assert_eq!(error(&with_dirs(&["--workers", "0"])), "workers");
+        assert_eq!(error(&with_dirs(&["--pool-size", "0"])), "pool_size");
+        assert_eq!(error(&with_dirs(&["--log-level", "web=loud"])), "log_level");
+        assert_eq!(
+            error(&with_dirs(&["--site-url", "ftp://bible.rs"])),
+            "site_url"
+        );
+        assert_eq!(error(&with_dirs(&["--backend", "postgres"])), "backend");
+        assert_eq!(error(&with_dirs(&["--tls-cert", "cert.pem"])), "tls_key");
+        assert_eq!(
+            error(&with_dirs(&["--tls-redirect-bind", "0.0.0.0:80"])),
+            "tls_cert"
+        );
+        assert_eq!(
+            error(&with_dirs(&["--search-rate-burst", "0"])),
+            "search_rate_burst"
+        );
+        assert_eq!(
+            error(&with_dirs(&["--rate-limit-allow", "10.0.0.0/8,local"])),
+            "rate_limit_allow"
+        );
+        assert_eq!(
+            error(&with_dirs(&["--client-ip-header", "X Real IP"])),
+            "client_ip_header"
+        );
+        assert_eq!(
+            error(&["--static-dir", "./nope", "--template-dir", "./templates"]),
+            "static_dir"
+        );
+    }
+
+    #[test]
+    fn log_directives() {
+        assert_eq!(invalid_log_directive("info"), None);
+        assert_eq!(invalid_log_directive("warn,web=debug,actix_web"), None);
+        assert_eq!(invalid_log_directive("web=debug/foo.*"), None);
+        assert_eq!(invalid_log_directive("warn, web=loud"), Some("web=loud"));
+    }
+}
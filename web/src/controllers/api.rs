@@ -1,65 +1,78 @@
-use actix_web::web;
+use actix_web::web::{self, Bytes};
 use actix_web::{HttpRequest, HttpResponse};
 
 use db::models::Reference;
-use db::{SwordDrillable, VerseFormat};
+use db::VerseFormat;
 
-use crate::controllers::SearchParams;
+use crate::cache::{Page, X_CACHE};
+use crate::controllers::{Representation, SearchParams};
 use crate::error::{Error, JsonError};
+use crate::logging;
 use crate::responder::{SearchResultData, VersesData};
 use crate::ServerData;
 
 /// Result for JSON API response handlers
 type ApiResult = Result<HttpResponse, JsonError>;
 
-pub async fn reference<SD>(
+pub async fn reference(
     data: web::Data<ServerData>,
     params: web::Path<(String,)>,
     req: HttpRequest,
-) -> ApiResult
-where
-    SD: SwordDrillable,
-{
+) -> ApiResult {
     let (path_reference,) = params.into_inner();
-    let db = data.db.to_owned();
     let books = &data.books;
     let raw_reference = path_reference.replace('/', ".");
 
     if let Ok(reference) = raw_reference.parse::<Reference>() {
-        let data_reference = reference.to_owned();
-        let result = web::block(move || {
-            SD::verses(&reference, VerseFormat::PlainText, &mut db.get().unwrap())
-        })
-        .await??;
+        logging::record("reference", reference.to_string());
+        let key = data.pages.key(
+            &reference,
+            Representation::Json,
+            &data.site.for_request(&req),
+            books,
+        );
+        if let Some(page) = key.as_ref().and_then(|k| data.pages.get(k)) {
+            return Ok(HttpResponse::Ok()
+                .content_type("application/json")
+                .insert_header((X_CACHE, "HIT"))
+                .body(page.body));
+        }
+
+        let result = data
+            .drill
+            .verses(&reference, VerseFormat::PlainText)
+            .await?;
+
+        let verses_data = VersesData::new(result, reference, books, &req);
+        let body = Bytes::from(serde_json::to_string(&verses_data).unwrap());
+        if let Some(key) = key {
+            data.pages.insert(key, Page::from(body.clone()));
+        }
 
-        let verses_data = VersesData::new(result, data_reference, books, &req);
-        Ok(HttpResponse::Ok().json(verses_data))
+        Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header((X_CACHE, "MISS"))
+            .body(body))
     } else {
         Err(Error::InvalidReference(raw_reference).into())
     }
 }
 
-pub async fn search<SD>(
+pub async fn search(
     data: web::Data<ServerData>,
     query: web::Query<SearchParams>,
     req: HttpRequest,
-) -> ApiResult
-where
-    SD: SwordDrillable,
-{
+) -> ApiResult {
+    logging::record("query", query.q.as_str());
     if let Ok(reference) = query.q.parse::<Reference>() {
-        let results = web::block(move || {
-            SD::verses(
-                &reference,
-                VerseFormat::PlainText,
-                &mut data.db.get().unwrap(),
-            )
-        })
-        .await??;
+        logging::record("reference", reference.to_string());
+        let results = data
+            .drill
+            .verses(&reference, VerseFormat::PlainText)
+            .await?;
         Ok(HttpResponse::Ok().json(SearchResultData::from_verses(results, &req)))
     } else {
-        let results =
-            web::block(move || SD::search(&query.q, &mut data.db.get().unwrap())).await??;
+        let results = data.drill.search(&query.q).await?;
         Ok(HttpResponse::Ok().json(SearchResultData::from_verses_fts(results, &req)))
     }
 }
@@ -0,0 +1,136 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use futures_util::stream;
+use serde_derive::Deserialize;
+
+use crate::controllers::check_translation;
+use crate::error::{Error, HtmlError};
+use crate::export::text::{Chunks, TextFormat};
+use crate::export::{epub, Range};
+use crate::logging;
+use crate::ServerData;
+
+/// Result for export download handlers
+type ExportResult = Result<HttpResponse, HtmlError>;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct ExportParams {
+    /// Book, books or passage to export, or nothing for the whole Bible.
+    range: Option<String>,
+}
+
+/// Header downloading a response as a file.
+fn attachment(filename: String) -> ContentDisposition {
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(filename)],
+    }
+}
+
+/// Downloads an EPUB of the Bible, or of a range of it.
+///
+/// EPUBs are built once per range and kept in the server's cache.
+pub async fn epub(
+    data: web::Data<ServerData>,
+    params: web::Path<(String,)>,
+    query: web::Query<ExportParams>,
+) -> ExportResult {
+    let (translation,) = params.into_inner();
+    check_translation(&translation)?;
+    if let Some(ref range) = query.range {
+        logging::record("range", range.as_str());
+    }
+
+    let drill = data.drill.as_ref();
+    let range = Range::find(drill, query.range.as_deref()).await?;
+    let out = data.epubs.get(drill, &range).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(epub::MEDIA_TYPE)
+        .insert_header(attachment(format!("{}.epub", range.file_stem())))
+        .body(out))
+}
+
+/// Downloads the full text of the Bible as a table of verses.
+///
+/// The verses are streamed from the database and sent in chunks, so the
+/// download is never all in memory and doesn't hold up a worker.
+pub async fn text(
+    data: web::Data<ServerData>,
+    params: web::Path<(String, String)>,
+) -> ExportResult {
+    let (translation, extension) = params.into_inner();
+    check_translation(&translation)?;
+    let format = TextFormat::from_extension(&extension)
+        .ok_or_else(|| Error::NotFound(format!("The '{}' format", extension)))?;
+
+    let chunks = Chunks::new(data.drill.as_ref(), format).await?;
+    let body = stream::unfold(chunks, |mut chunks| async move {
+        let chunk = chunks.next().await?;
+        Some((chunk.map(Bytes::from).map_err(HtmlError::from), chunks))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(attachment(format!("{}.{}", translation, extension)))
+        .streaming(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+
+    use crate::test::download_response;
+
+    #[actix_web::test]
+    async fn epub() {
+        let (status, headers, body) = download_response("/api/export/kjv.epub?range=psalms").await;
+        assert!(status.is_success());
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "application/epub+zip"
+        );
+        assert_eq!(
+            headers.get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"psalms.epub\""
+        );
+        assert!(body.starts_with(b"PK"));
+
+        // Stamped with the content version, not the time of the download
+        let (_, _, again) = download_response("/api/export/kjv.epub?range=psalms").await;
+        assert_eq!(body, again);
+
+        let (status, _, _) = download_response("/api/export/nkjv.epub").await;
+        assert_eq!(status.as_u16(), 404);
+    }
+
+    #[actix_web::test]
+    async fn text() {
+        let (status, headers, body) = download_response("/api/export/kjv.csv").await;
+        assert!(status.is_success());
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(
+            headers.get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"kjv.csv\""
+        );
+        assert!(body.starts_with(b"book,chapter,verse,text\nPsalms,119,104,"));
+
+        let (status, _, body) = download_response("/api/export/kjv.jsonl").await;
+        assert!(status.is_success());
+        assert_eq!(
+            body.split(|b| *b == b'\n')
+                .filter(|l| !l.is_empty())
+                .count(),
+            2
+        );
+
+        let (status, _, _) = download_response("/api/export/kjv.pdf").await;
+        assert_eq!(status.as_u16(), 404);
+        let (status, _, _) = download_response("/api/export/nkjv.txt").await;
+        assert_eq!(status.as_u16(), 404);
+    }
+}
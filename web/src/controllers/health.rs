@@ -0,0 +1,57 @@
+use actix_web::{web, HttpResponse};
+use log::error;
+
+use crate::ServerData;
+
+/// Handles HTTP requests for the liveness probe.
+///
+/// Answering at all means the process is up.
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("ok")
+}
+
+/// Handles HTTP requests for the readiness probe.
+///
+/// The server is ready once the Bible can be queried, which for SQLite
+/// means a pooled connection can be checked out and the books read.
+pub async fn readyz(data: web::Data<ServerData>) -> HttpResponse {
+    match data.drill.ready().await {
+        Ok(()) => HttpResponse::Ok().content_type("text/plain").body("ready"),
+        Err(e) => {
+            error!("Not ready: {}", e);
+            HttpResponse::ServiceUnavailable()
+                .content_type("text/plain")
+                .body(e.to_string())
+        }
+    }
+}
+
+/// Handles HTTP requests for metrics in the Prometheus text format.
+pub async fn metrics(data: web::Data<ServerData>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(data.metrics.render(data.drill.pool_stats()))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+
+    use crate::test::{html_response, metrics_response, page_status};
+
+    #[actix_web::test]
+    async fn probes() {
+        assert_eq!(html_response("/healthz").await, "ok");
+        assert_eq!(html_response("/readyz").await, "ready");
+    }
+
+    #[actix_web::test]
+    async fn metrics() {
+        let (status, body) = metrics_response("/metrics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("# TYPE biblers_db_pool_connections gauge"));
+
+        // Metrics aren't public
+        assert_eq!(page_status("/metrics").await, StatusCode::NOT_FOUND);
+    }
+}
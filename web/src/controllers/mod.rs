@@ -1,9 +1,115 @@
+use actix_web::http::header::{Accept, Header};
+use actix_web::HttpRequest;
 use serde_derive::Deserialize;
 
+use crate::cache::TRANSLATION;
+use crate::error::Error;
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct SearchParams {
     q: String,
 }
 
+/// Checks that a translation in a path is the one served.
+pub fn check_translation(translation: &str) -> Result<(), Error> {
+    if translation.eq_ignore_ascii_case(TRANSLATION) {
+        Ok(())
+    } else {
+        Err(Error::NotFound(format!(
+            "The '{}' translation",
+            translation
+        )))
+    }
+}
+
+/// Representations a view can be rendered as.
+///
+/// The representation is negotiated from the request's `Accept` header,
+/// falling back to HTML when nothing else matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Representation {
+    Html,
+    Json,
+    Markdown,
+    PlainText,
+}
+
+impl Representation {
+    /// Negotiates the representation for a request.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let accept = match Accept::parse(req) {
+            Ok(accept) => accept,
+            Err(_) => return Representation::Html,
+        };
+
+        accept
+            .ranked()
+            .iter()
+            .find_map(|mime| match (mime.type_(), mime.subtype().as_str()) {
+                (mime::TEXT, "html") | (mime::TEXT, "*") | (mime::STAR, _) => {
+                    Some(Representation::Html)
+                }
+                (mime::APPLICATION, "json") => Some(Representation::Json),
+                (mime::TEXT, "markdown") => Some(Representation::Markdown),
+                (mime::TEXT, "plain") => Some(Representation::PlainText),
+                _ => None,
+            })
+            .unwrap_or(Representation::Html)
+    }
+
+    /// The `Content-Type` of a response in this representation.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Representation::Html => "text/html",
+            Representation::Json => "application/json",
+            Representation::Markdown => "text/markdown; charset=utf-8",
+            Representation::PlainText => "text/plain; charset=utf-8",
+        }
+    }
+}
+
 pub mod api;
+pub mod download;
+pub mod health;
+pub mod pwa;
+pub mod sitemap;
 pub mod view;
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::test::TestRequest;
+
+    use super::Representation;
+
+    #[test]
+    fn representation() {
+        [
+            (None, Representation::Html),
+            (Some("*/*"), Representation::Html),
+            (
+                Some("text/html,application/xhtml+xml"),
+                Representation::Html,
+            ),
+            (Some("application/json"), Representation::Json),
+            (Some("text/plain"), Representation::PlainText),
+            (Some("text/markdown"), Representation::Markdown),
+            (
+                Some("text/html;q=0.5, text/markdown"),
+                Representation::Markdown,
+            ),
+            (Some("image/png"), Representation::Html),
+        ]
+        .into_iter()
+        .for_each(|(accept, expected)| {
+            let mut req = TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header((header::ACCEPT, accept));
+            }
+            assert_eq!(
+                Representation::from_request(&req.to_http_request()),
+                expected
+            );
+        });
+    }
+}
@@ -0,0 +1,164 @@
+use actix_web::http::header::{
+    self, AcceptEncoding, CacheControl, CacheDirective, ContentEncoding, Encoding, Header,
+    Preference,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+use serde_derive::{Deserialize, Serialize};
+
+use db::VerseFormat;
+
+use crate::controllers::check_translation;
+use crate::error::{Error, HtmlError};
+use crate::offline;
+use crate::ServerData;
+
+/// Result for offline data response handlers
+type OfflineResult = Result<HttpResponse, HtmlError>;
+
+/// Format of the verses in a bundle.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    #[default]
+    Text,
+    Html,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct BundleParams {
+    #[serde(default)]
+    format: BundleFormat,
+}
+
+/// Data of the service worker template.
+#[derive(Serialize)]
+struct ServiceWorkerData<'a> {
+    cache_name: &'a str,
+    /// JSON array of the URLs to precache.
+    precache: String,
+    /// JSON string of the bundle's URL.
+    bundle_url: String,
+}
+
+/// Whether a client accepts a gzipped response.
+fn accepts_gzip(req: &HttpRequest) -> bool {
+    AcceptEncoding::parse(req).is_ok_and(|accept| {
+        accept
+            .ranked()
+            .contains(&Preference::Specific(Encoding::gzip()))
+    })
+}
+
+/// Renders the service worker.
+///
+/// The worker is generated with the files it precaches and a cache name
+/// hashed from them, so browsers replace its cache whenever the content
+/// or static files change.
+pub fn service_worker_script(data: &ServerData) -> Result<String, Error> {
+    let offline = &data.offline;
+    let worker = ServiceWorkerData {
+        cache_name: &offline.cache_name,
+        precache: serde_json::to_string(&offline.precache).unwrap(),
+        bundle_url: serde_json::to_string(&offline.bundle_url).unwrap(),
+    };
+    data.template.render("sw.js", &worker).map_err(|e| {
+        error!("{}", e);
+        Error::Template
+    })
+}
+
+/// Handles HTTP requests for the service worker.
+pub async fn service_worker(data: web::Data<ServerData>) -> OfflineResult {
+    let body = service_worker_script(&data)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/javascript")
+        // Browsers look for a new worker on every visit
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .body(body))
+}
+
+/// Handles HTTP requests for the bundle of a whole translation.
+///
+/// The bundle is JSON of the books and every verse, as plain text or
+/// with `?format=html` as HTML. It is built and gzipped once, then sent
+/// as is to the clients accepting gzip.
+pub async fn bundle(
+    data: web::Data<ServerData>,
+    params: web::Path<(String,)>,
+    query: web::Query<BundleParams>,
+    req: HttpRequest,
+) -> OfflineResult {
+    let (translation,) = params.into_inner();
+    check_translation(&translation)?;
+
+    let format = match query.format {
+        BundleFormat::Text => VerseFormat::PlainText,
+        BundleFormat::Html => VerseFormat::Html,
+    };
+    let bundle = data
+        .offline
+        .bundle(data.drill.as_ref(), &data.books, format)
+        .await?;
+
+    let mut builder = HttpResponse::Ok();
+    builder
+        .content_type("application/json")
+        .insert_header((header::VARY, "Accept-Encoding"));
+    if accepts_gzip(&req) {
+        Ok(builder.insert_header(ContentEncoding::Gzip).body(bundle))
+    } else {
+        let json = offline::decompress(&bundle).map_err(|e| Error::Export(e.to_string()))?;
+        Ok(builder.body(json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use actix_web::http::header;
+    use flate2::read::GzDecoder;
+    use serde_json::Value;
+
+    use crate::test::offline_response;
+
+    #[actix_web::test]
+    async fn service_worker() {
+        let (_, headers, body) = offline_response("/sw.js", None).await;
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "application/javascript"
+        );
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("// Generated by the server"));
+        assert!(body.contains("const CACHE_NAME = \"biblers-"));
+        assert!(body.contains("\"/static/js/main.js\""));
+        assert!(body.contains("const BUNDLE_URL = \"/api/bundle/kjv.json?v="));
+    }
+
+    #[actix_web::test]
+    async fn bundle() {
+        let (status, headers, body) =
+            offline_response("/api/bundle/kjv.json", Some("gzip, br")).await;
+        assert!(status.is_success());
+        assert_eq!(headers.get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        let mut json = String::new();
+        GzDecoder::new(&body[..]).read_to_string(&mut json).unwrap();
+        let bundle: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(bundle["translation"], "kjv");
+        assert_eq!(bundle["html"], false);
+        assert_eq!(bundle["books"][0]["name"], "Psalms");
+        assert_eq!(bundle["verses"][0][2], 105);
+
+        // Clients that don't accept gzip get the JSON itself
+        let (_, headers, body) = offline_response("/api/bundle/kjv.json?format=html", None).await;
+        assert!(headers.get(header::CONTENT_ENCODING).is_none());
+        let bundle: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(bundle["html"], true);
+
+        let (status, _, _) = offline_response("/api/bundle/nkjv.json", None).await;
+        assert_eq!(status.as_u16(), 404);
+    }
+}
@@ -0,0 +1,168 @@
+use actix_web::error::UrlGenerationError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use url::Url;
+
+use db::models::Book;
+
+use crate::format::escape;
+use crate::responder::{Site, Urls};
+use crate::ServerData;
+
+/// Result for sitemap and robots response handlers
+type SitemapResult = actix_web::Result<HttpResponse>;
+
+/// Result of rendering a sitemap or robots document.
+type DocumentResult = Result<String, UrlGenerationError>;
+
+/// Namespace of sitemap and sitemap index documents.
+const SITEMAP_NS: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Makes a generated route URL absolute on the site, matching the
+/// pages' canonical URLs.
+fn loc(site: &Site, url: Result<Url, UrlGenerationError>) -> DocumentResult {
+    Ok(match url?.path() {
+        "/" => site.url_for(""),
+        path => site.url_for(path),
+    })
+}
+
+/// Renders a document of `<loc>` entries wrapped in `tag` elements.
+fn document(root: &str, tag: &str, locs: Vec<String>) -> String {
+    let mut body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{} xmlns=\"{}\">\n",
+        root, SITEMAP_NS
+    );
+    for loc in locs {
+        body.push_str(&format!(
+            "<{tag}>\n    <loc>{}</loc>\n</{tag}>\n",
+            escape(&loc),
+            tag = tag
+        ));
+    }
+    body.push_str(&format!("</{}>\n", root));
+    body
+}
+
+/// Responds with an XML document.
+fn xml(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+/// Renders the sitemap index.
+///
+/// Lists a sitemap for the site's own pages, and one per book with the
+/// book's chapters, which keeps every sitemap well under the limit of
+/// 50,000 URLs.
+pub fn index_document(books: &[Book], site: &Site, urls: &impl Urls) -> DocumentResult {
+    let mut locs = vec![loc(site, urls.url_for("sitemap_pages", &[]))?];
+    for book in books {
+        locs.push(loc(site, urls.url_for("sitemap_book", &[&book.name]))?);
+    }
+
+    Ok(document("sitemapindex", "sitemap", locs))
+}
+
+/// Handles HTTP requests for the sitemap index.
+pub async fn index(data: web::Data<ServerData>, req: HttpRequest) -> SitemapResult {
+    let site = data.site.for_request(&req);
+    Ok(xml(index_document(&data.books, &site, &req)?))
+}
+
+/// Renders the sitemap of pages other than books.
+pub fn pages_document(site: &Site, urls: &impl Urls) -> DocumentResult {
+    let locs = vec![
+        loc(site, urls.url_for("bible", &[]))?,
+        loc(site, urls.url_for("about", &[]))?,
+    ];
+
+    Ok(document("urlset", "url", locs))
+}
+
+/// Handles HTTP requests for the sitemap of pages other than books.
+pub async fn pages(data: web::Data<ServerData>, req: HttpRequest) -> SitemapResult {
+    let site = data.site.for_request(&req);
+    Ok(xml(pages_document(&site, &req)?))
+}
+
+/// Renders the sitemap of a book and its chapters.
+pub fn book_document(book: &Book, site: &Site, urls: &impl Urls) -> DocumentResult {
+    let mut locs = vec![loc(site, urls.url_for("book", &[&book.name]))?];
+    for chapter in 1..=book.chapter_count {
+        locs.push(loc(
+            site,
+            urls.url_for("reference", &[&format!("{}/{}", book.name, chapter)]),
+        )?);
+    }
+
+    Ok(document("urlset", "url", locs))
+}
+
+/// Handles HTTP requests for the sitemap of a book and its chapters.
+pub async fn book(
+    data: web::Data<ServerData>,
+    params: web::Path<(String,)>,
+    req: HttpRequest,
+) -> SitemapResult {
+    let (book_name,) = params.into_inner();
+    let book = match data.books.iter().find(|b| b.name == book_name) {
+        Some(book) => book,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let site = data.site.for_request(&req);
+    Ok(xml(book_document(book, &site, &req)?))
+}
+
+/// Renders `robots.txt`, pointing crawlers at the sitemap index.
+pub fn robots_text(site: &Site, urls: &impl Urls) -> DocumentResult {
+    let sitemap = loc(site, urls.url_for("sitemap", &[]))?;
+
+    Ok(format!(
+        "User-agent: *\nDisallow: /search/\nDisallow: /api/\n\nSitemap: {}\n",
+        sitemap
+    ))
+}
+
+/// Handles HTTP requests for `robots.txt`.
+pub async fn robots(data: web::Data<ServerData>, req: HttpRequest) -> SitemapResult {
+    let site = data.site.for_request(&req);
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(robots_text(&site, &req)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::html_response;
+
+    #[actix_web::test]
+    async fn index() {
+        let result = html_response("/sitemap.xml").await;
+        assert!(result.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex"));
+        assert!(result.contains("<loc>https://bible.rs/sitemap/pages.xml</loc>"));
+        assert!(result.contains("<loc>https://bible.rs/sitemap/Song%20of%20Solomon.xml</loc>"));
+        assert_eq!(result.matches("<sitemap>").count(), 67);
+    }
+
+    #[actix_web::test]
+    async fn sitemaps() {
+        let result = html_response("/sitemap/pages.xml").await;
+        assert!(result.contains("<loc>https://bible.rs</loc>"));
+        assert!(result.contains("<loc>https://bible.rs/about</loc>"));
+
+        let result = html_response("/sitemap/Psalms.xml").await;
+        assert!(result.contains("<loc>https://bible.rs/Psalms</loc>"));
+        assert!(result.contains("<loc>https://bible.rs/Psalms/150</loc>"));
+        assert_eq!(result.matches("<url>").count(), 151);
+
+        assert!(html_response("/sitemap/Psalm.xml").await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn robots() {
+        let result = html_response("/robots.txt").await;
+        assert!(result.ends_with("Sitemap: https://bible.rs/sitemap.xml\n"));
+    }
+}
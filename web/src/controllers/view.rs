@@ -1,64 +1,192 @@
-use actix_web::web;
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use serde::Serialize;
 
 use db::models::Reference;
-use db::{SwordDrillable, VerseFormat};
+use db::VerseFormat;
 
-use crate::controllers::SearchParams;
+use crate::cache::{Page, X_CACHE};
+use crate::controllers::{Representation, SearchParams};
 use crate::error::{Error, HtmlError};
+use crate::logging;
 use crate::responder::*;
 use crate::ServerData;
 
 /// Result for HTML response handlers
 type ViewResult = Result<HttpResponse, HtmlError>;
 
-/// Handles HTTP requests for the about page.
-pub async fn about(data: web::Data<ServerData>) -> ViewResult {
-    let body = TemplateData::new(EmptyData, Meta::for_about()).to_html("about", &data.template)?;
+/// Starts a successful response for a negotiated representation.
+fn negotiated(representation: Representation) -> HttpResponseBuilder {
+    let mut builder = HttpResponse::Ok();
+    builder
+        .content_type(representation.content_type())
+        .insert_header((header::VARY, "Accept"));
+    builder
+}
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+/// Finishes a response with a page, allowing its inline scripts in the
+/// [security headers](crate::middleware::SecurityHeaders).
+fn page(mut builder: HttpResponseBuilder, page: Page) -> HttpResponse {
+    builder.extensions_mut().insert(page.scripts);
+    builder.body(page.body)
 }
 
-/// Handles HTTP requests for a list of all books.
+/// Renders an HTML page and gets the hashes of its inline scripts.
+fn render_page<T: Serialize>(
+    data: T,
+    meta: Meta,
+    tpl_name: &str,
+    template: &handlebars::Handlebars,
+) -> Result<Page, Error> {
+    let scripts = meta.script_hashes();
+    let body = TemplateData::new(data, meta).to_html(tpl_name, template)?;
+    Ok(Page {
+        body: Bytes::from(body),
+        scripts,
+    })
+}
+
+/// Renders the non-HTML representations of view data.
 ///
-/// Return an HTML page that lists all books in the Bible.
-pub async fn all_books<SD>(data: web::Data<ServerData>, req: HttpRequest) -> ViewResult
+/// Returns `None` for HTML, which each view renders with its own template.
+fn render_text<T>(representation: Representation, data: &T) -> Option<String>
 where
-    SD: SwordDrillable,
+    T: Serialize + ToText,
 {
-    let db = data.db.to_owned();
-    let books = web::block(move || SD::all_books(&mut db.get().unwrap())).await??;
+    match representation {
+        Representation::Html => None,
+        Representation::Json => Some(serde_json::to_string(data).unwrap()),
+        Representation::Markdown => Some(data.to_markdown()),
+        Representation::PlainText => Some(data.to_plain_text()),
+    }
+}
+
+/// Renders the about page.
+pub fn about_page(data: &ServerData, site: &Site) -> Result<Page, Error> {
+    render_page(EmptyData, Meta::for_about(site), "about", &data.template)
+}
+
+/// Handles HTTP requests for the about page.
+pub async fn about(data: web::Data<ServerData>, req: HttpRequest) -> ViewResult {
+    let about = about_page(&data, &data.site.for_request(&req))?;
+
+    let mut builder = HttpResponse::Ok();
+    builder.content_type("text/html");
+    Ok(page(builder, about))
+}
+
+/// Renders the list of all books in a representation.
+pub async fn all_books_page(
+    data: &ServerData,
+    representation: Representation,
+    site: &Site,
+    urls: &impl Urls,
+) -> Result<Page, Error> {
+    let books = data.drill.all_books().await?;
+
+    let books_data = AllBooksData::new(books, urls);
+    if let Some(body) = render_text(representation, &books_data) {
+        return Ok(Page::from(Bytes::from(body)));
+    }
+
+    let meta = Meta::for_all_books(site, &books_data.links);
+    render_page(books_data, meta, "all-books", &data.template)
+}
 
-    let books_data = AllBooksData::new(books, &req);
-    let meta = Meta::for_all_books(&books_data.links);
-    let body = TemplateData::new(books_data, meta).to_html("all-books", &data.template)?;
+/// Handles HTTP requests for a list of all books.
+///
+/// Return an HTML page that lists all books in the Bible, or the
+/// representation negotiated by the `Accept` header.
+pub async fn all_books(data: web::Data<ServerData>, req: HttpRequest) -> ViewResult {
+    let representation = Representation::from_request(&req);
+    let site = data.site.for_request(&req);
+    let all_books = all_books_page(&data, representation, &site, &req).await?;
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+    Ok(page(negotiated(representation), all_books))
+}
+
+/// Renders a book's page in a representation.
+pub async fn book_page(
+    data: &ServerData,
+    book_name: &str,
+    representation: Representation,
+    site: &Site,
+    urls: &impl Urls,
+) -> Result<Page, Error> {
+    let result = data.drill.book(book_name).await?;
+    let book_data = BookData::new(result, &data.books, urls);
+    if let Some(body) = render_text(representation, &book_data) {
+        return Ok(Page::from(Bytes::from(body)));
+    }
+
+    let meta = Meta::for_book(site, &book_data.book, &book_data.links);
+    render_page(&book_data, meta, "book", &data.template)
 }
 
 /// Handles HTTP requests for a book (e.g. /John)
 ///
 /// Assume the path parameter is a Bible book, and get an HTML response
-/// that has book metadata and a list of chapters.
-pub async fn book<SD>(
+/// that has book metadata and a list of chapters. Other representations
+/// can be requested with the `Accept` header.
+pub async fn book(
     data: web::Data<ServerData>,
     params: web::Path<(String,)>,
     req: HttpRequest,
-) -> ViewResult
-where
-    SD: SwordDrillable,
-{
+) -> ViewResult {
+    let representation = Representation::from_request(&req);
     let (book_name,) = params.into_inner();
-    let db = data.db.to_owned();
-    let result = web::block(move || SD::book(&book_name, &mut db.get().unwrap())).await??;
-    let book_data = BookData::new(result, &data.books, &req);
-    let body = TemplateData::new(
-        &book_data,
-        Meta::for_book(&book_data.book, &book_data.links),
-    )
-    .to_html("book", &data.template)?;
-
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+    let site = data.site.for_request(&req);
+    let book = book_page(&data, &book_name, representation, &site, &req).await?;
+
+    Ok(page(negotiated(representation), book))
+}
+
+/// Renders a reference page, taking it from the page cache when it is
+/// there and caching it otherwise. Gets the page and whether it was
+/// cached, or `None` when the reference has no verses.
+///
+/// Pages are rendered for a request, or ahead of requests when warming
+/// up the cache.
+pub async fn reference_page(
+    data: &ServerData,
+    reference: Reference,
+    representation: Representation,
+    site: &Site,
+    urls: &impl Urls,
+) -> Result<Option<(Page, bool)>, Error> {
+    let format = match representation {
+        Representation::Html => VerseFormat::Html,
+        _ => VerseFormat::PlainText,
+    };
+    let books = &data.books;
+    let key = data.pages.key(&reference, representation, site, books);
+    if let Some(cached) = key.as_ref().and_then(|k| data.pages.get(k)) {
+        return Ok(Some((cached, true)));
+    }
+
+    let result = data.drill.verses(&reference, format).await?;
+    let verses_data = VersesData::new(result, reference, books, urls);
+    if verses_data.verses.is_empty() {
+        return Ok(None);
+    }
+
+    let chapter = match render_text(representation, &verses_data) {
+        Some(body) => Page::from(Bytes::from(body)),
+        None => {
+            let meta = Meta::for_reference(
+                site,
+                &verses_data.reference,
+                &verses_data.verses,
+                &verses_data.links,
+            );
+            render_page(&verses_data, meta, "chapter", &data.template)?
+        }
+    };
+    if let Some(key) = key {
+        data.pages.insert(key, chapter.clone());
+    }
+    Ok(Some((chapter, false)))
 }
 
 /// Handles HTTP requests for references (e.g. /John/1/1).
@@ -66,40 +194,31 @@ where
 /// Parse the URL path for a string that would indicate a reference.
 /// If the path parses to a reference, then it is passed to the database
 /// layer and looked up, returning an HTTP response with the verse body.
-pub async fn reference<SD>(
+///
+/// JSON, plain text and Markdown are returned instead of HTML when
+/// requested with the `Accept` header. These use the plain text verses,
+/// which makes the JSON identical to the API payload.
+///
+/// Rendered pages are kept in the server's page cache.
+pub async fn reference(
     data: web::Data<ServerData>,
     params: web::Path<(String,)>,
     req: HttpRequest,
-) -> ViewResult
-where
-    SD: SwordDrillable,
-{
+) -> ViewResult {
+    let representation = Representation::from_request(&req);
     let (path_reference,) = params.into_inner();
-    let db = data.db.to_owned();
-    let books = &data.books;
     let raw_reference = path_reference.replace('/', ".");
 
     if let Ok(reference) = raw_reference.parse::<Reference>() {
-        let data_reference = reference.to_owned();
-        let result =
-            web::block(move || SD::verses(&reference, VerseFormat::Html, &mut db.get().unwrap()))
-                .await??;
-        let verses_data = VersesData::new(result, data_reference, books, &req);
-
-        if verses_data.verses.is_empty() {
-            return Err(Error::InvalidReference(raw_reference).into());
-        }
+        logging::record("reference", reference.to_string());
+        let site = data.site.for_request(&req);
+        let (chapter, cached) = reference_page(&data, reference, representation, &site, &req)
+            .await?
+            .ok_or(Error::InvalidReference(raw_reference))?;
 
-        let body = TemplateData::new(
-            &verses_data,
-            Meta::for_reference(
-                &verses_data.reference,
-                &verses_data.verses,
-                &verses_data.links,
-            ),
-        )
-        .to_html("chapter", &data.template)?;
-        Ok(HttpResponse::Ok().content_type("text/html").body(body))
+        let mut builder = negotiated(representation);
+        builder.insert_header((X_CACHE, if cached { "HIT" } else { "MISS" }));
+        Ok(page(builder, chapter))
     } else {
         Err(Error::InvalidReference(raw_reference).into())
     }
@@ -109,28 +228,31 @@ where
 ///
 /// Return an HTML page with search results based on the `q` query
 /// parameter.
-pub async fn search<SD>(
+pub async fn search(
     data: web::Data<ServerData>,
     query: web::Query<SearchParams>,
     req: HttpRequest,
-) -> ViewResult
-where
-    SD: SwordDrillable,
-{
-    let db = data.db.to_owned();
+) -> ViewResult {
     let q = query.q.to_owned();
-    let result = web::block(move || SD::search(&query.q, &mut db.get().unwrap())).await??;
-    let body = TemplateData::new(
+    logging::record("query", q.as_str());
+    let result = data.drill.search(&q).await?;
+    let results = render_page(
         SearchResultData::from_verses_fts(result, &req),
-        Meta::for_search(&q, &req.uri().to_string()),
-    )
-    .to_html("search-results", &data.template)?;
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+        Meta::for_search(&data.site.for_request(&req), &q, &req.uri().to_string()),
+        "search-results",
+        &data.template,
+    )?;
+
+    let mut builder = HttpResponse::Ok();
+    builder.content_type("text/html");
+    Ok(page(builder, results))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::test::html_response;
+    use super::*;
+    use crate::responder::{RoutePaths, VersesData};
+    use crate::test::{html_response, negotiated_response, page_data};
 
     #[actix_web::test]
     async fn about() {
@@ -154,5 +276,59 @@ mod tests {
     async fn reference() {
         let result = html_response("/Psalms/119").await;
         assert!(result.contains("NUN. Thy word is a lamp unto my feet, and a light unto my path."));
+        assert!(result.contains(r#"<link rel="canonical" href="https://bible.rs/Psalms/119">"#));
+    }
+
+    #[actix_web::test]
+    async fn reference_rendered_ahead() {
+        let data = page_data();
+        let reference: Reference = "Psalms 119".parse().unwrap();
+        let urls = RoutePaths::new();
+        let render = || {
+            reference_page(
+                &data,
+                reference.clone(),
+                Representation::Html,
+                &data.site,
+                &urls,
+            )
+        };
+
+        let (page, cached) = render().await.unwrap().unwrap();
+        assert!(!cached);
+        assert_eq!(page.body, html_response("/Psalms/119").await);
+        let (_, cached) = render().await.unwrap().unwrap();
+        assert!(cached);
+    }
+
+    #[actix_web::test]
+    async fn search() {
+        let result = html_response("/search?q=%3Cb%3Elight").await;
+        assert!(result.contains(
+            "| NUN. Thy word is a lamp unto my feet, and a <em>light</em> unto my path.</a>"
+        ));
+
+        // The query is escaped wherever it's shown
+        assert!(result.contains("&lt;b&gt;light"));
+        assert!(!result.contains("<b>"));
+    }
+
+    #[actix_web::test]
+    async fn negotiated() {
+        let result = negotiated_response("/Psalms/119", "application/json").await;
+        let result: VersesData = serde_json::from_str(&result).unwrap();
+        assert_eq!(result.reference_string, "Psalms 119");
+
+        let result = negotiated_response("/Psalms/119", "text/plain").await;
+        assert!(result.starts_with("Psalms 119\n105\tNUN. Thy word"));
+
+        let result = negotiated_response("/Psalms/119", "text/markdown").await;
+        assert!(result.starts_with("# Psalms 119\n\n**105** NUN. Thy word"));
+
+        let result = negotiated_response("/Psalms", "text/plain").await;
+        assert!(result.starts_with("Psalms\nPsalms 1\n"));
+
+        let result = negotiated_response("/", "text/markdown").await;
+        assert!(result.contains("- [Psalms](/Psalms)"));
     }
 }
@@ -1,16 +1,19 @@
 use std::convert::From;
+use std::error::Error as StdError;
+use std::path::Path;
 
-use actix_web::error::BlockingError;
 use actix_web::HttpResponse;
 use actix_web::ResponseError;
 use handlebars::Handlebars;
-use lazy_static::lazy_static;
 use log::error;
+use once_cell::sync::OnceCell;
 use thiserror::Error;
 
 use db::DbError;
 
-use crate::responder::{ErrorData, Meta, SearchResultData, TemplateData};
+use crate::config::DEFAULT_TEMPLATE_DIR;
+use crate::export::ExportError;
+use crate::responder::{ErrorData, Meta, SearchResultData, Site, TemplateData};
 
 /// Error type for the Bible.rs application.
 #[derive(Clone, Error, Debug)]
@@ -18,6 +21,9 @@ pub enum Error {
     #[error("'{0}' was not found.")]
     BookNotFound(String),
 
+    #[error("{0} was not found.")]
+    NotFound(String),
+
     #[error("There was a database error. Root cause: {0}")]
     Db(String),
 
@@ -26,6 +32,18 @@ pub enum Error {
 
     #[error("There was an error rendering the HTML page.")]
     Template,
+
+    #[error("There was an error exporting the Bible. Root cause: {0}")]
+    Export(String),
+}
+
+impl From<ExportError> for Error {
+    fn from(f: ExportError) -> Self {
+        match f {
+            ExportError::Db(e) => e.into(),
+            ExportError::Io(e) => Error::Export(e.to_string()),
+        }
+    }
 }
 
 impl From<DbError> for Error {
@@ -35,7 +53,8 @@ impl From<DbError> for Error {
             DbError::BookNotFound { book } => Error::BookNotFound(book),
             DbError::Migration { cause }
             | DbError::Other { cause }
-            | DbError::ConnectionPool { cause } => Error::Db(cause),
+            | DbError::ConnectionPool { cause }
+            | DbError::Connection { cause, .. } => Error::Db(cause),
         }
     }
 }
@@ -54,7 +73,7 @@ impl From<DbError> for JsonError {
 impl ResponseError for JsonError {
     fn error_response(&self) -> HttpResponse {
         match &self.0 {
-            Error::Template => {
+            Error::Template | Error::Export(_) => {
                 error!("Unhandled: {}", &self.0);
                 HttpResponse::InternalServerError().json(ErrorData::from_error(&self.0))
             }
@@ -63,6 +82,7 @@ impl ResponseError for JsonError {
                 HttpResponse::InternalServerError().json(ErrorData::new(cause))
             }
             Error::BookNotFound { .. } => HttpResponse::Ok().json(SearchResultData::empty()),
+            Error::NotFound(_) => HttpResponse::NotFound().json(ErrorData::from_error(&self.0)),
             Error::InvalidReference { .. } => {
                 HttpResponse::BadRequest().json(ErrorData::from_error(&self.0))
             }
@@ -70,21 +90,39 @@ impl ResponseError for JsonError {
     }
 }
 
-impl From<BlockingError> for JsonError {
-    fn from(f: BlockingError) -> Self {
-        f.into()
+/// Templates and site of HTML error pages.
+struct ErrorPages {
+    site: Site,
+    template: Handlebars<'static>,
+}
+
+static ERROR_PAGES: OnceCell<ErrorPages> = OnceCell::new();
+
+impl ErrorPages {
+    fn new(dir: &Path, site: &Site) -> Result<Self, Box<dyn StdError>> {
+        let mut template = Handlebars::new();
+        template.register_template_file("base", dir.join("base.hbs"))?;
+        template.register_template_file("error", dir.join("error.hbs"))?;
+        Ok(Self {
+            site: site.to_owned(),
+            template,
+        })
+    }
+
+    fn get() -> &'static Self {
+        ERROR_PAGES
+            .get_or_init(|| Self::new(Path::new(DEFAULT_TEMPLATE_DIR), &Site::default()).unwrap())
     }
 }
 
-lazy_static! {
-    static ref ERR_TPL: Handlebars<'static> = {
-        let mut tpl = Handlebars::new();
-        tpl.register_template_file("base", "./web/templates/base.hbs")
-            .unwrap();
-        tpl.register_template_file("error", "./web/templates/error.hbs")
-            .unwrap();
-        tpl
-    };
+/// Registers the templates in the given directory and the site for HTML
+/// error pages.
+///
+/// Without this, the default template directory and site are used the
+/// first time an error page is rendered.
+pub fn register_error_pages(dir: &Path, site: &Site) -> Result<(), Box<dyn StdError>> {
+    let _ = ERROR_PAGES.set(ErrorPages::new(dir, site)?);
+    Ok(())
 }
 
 #[derive(Error, Debug)]
@@ -98,27 +136,28 @@ impl From<DbError> for HtmlError {
     }
 }
 
+impl From<ExportError> for HtmlError {
+    fn from(f: ExportError) -> Self {
+        HtmlError(f.into())
+    }
+}
+
 impl ResponseError for HtmlError {
     fn error_response(&self) -> HttpResponse {
-        let body = TemplateData::new(ErrorData::from_error(&self.0), Meta::for_error())
-            .to_html("error", &ERR_TPL)
+        let pages = ErrorPages::get();
+        let body = TemplateData::new(ErrorData::from_error(&self.0), Meta::for_error(&pages.site))
+            .to_html("error", &pages.template)
             .unwrap();
 
         match self.0 {
-            Error::Db { .. } | Error::Template => {
+            Error::Db { .. } | Error::Template | Error::Export(_) => {
                 error!("Unhandled: {}", &self.0);
                 HttpResponse::InternalServerError()
             }
-            Error::BookNotFound { .. } => HttpResponse::NotFound(),
+            Error::BookNotFound { .. } | Error::NotFound(_) => HttpResponse::NotFound(),
             Error::InvalidReference { .. } => HttpResponse::BadRequest(),
         }
         .content_type("text/html")
         .body(body)
     }
 }
-
-impl From<BlockingError> for HtmlError {
-    fn from(f: BlockingError) -> Self {
-        f.into()
-    }
-}
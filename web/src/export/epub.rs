@@ -0,0 +1,330 @@
+//! EPUB 3 books of the Bible for e-readers.
+
+use std::fmt::Write as _;
+use std::io::{Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use db::models::{Book, Word};
+use db::SwordDrillable;
+
+use crate::cache::TRANSLATION;
+use crate::export::{ExportError, Range, LANGUAGE, TRANSLATION_NAME};
+use crate::format::escape;
+
+/// Media type of EPUB files.
+pub const MEDIA_TYPE: &str = "application/epub+zip";
+
+/// Container pointing readers to the package document.
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Style sheet of the documents.
+const STYLE: &str = "\
+h1, h2 { text-align: center; }
+p { text-indent: 1em; margin: 0; }
+sup.verse { font-size: 0.6em; font-weight: bold; }
+nav ol { list-style: none; }
+nav ol ol li { display: inline; margin-right: 0.5em; }
+";
+
+/// Name of the document of a book in the publication.
+fn document(book: &Book) -> String {
+    format!("book-{:02}.xhtml", book.id)
+}
+
+/// Start of an XHTML content document.
+fn head(title: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         xml:lang=\"{0}\" lang=\"{0}\">\n\
+         <head>\n<meta charset=\"UTF-8\"/>\n<title>{1}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n",
+        LANGUAGE,
+        escape(title)
+    )
+}
+
+/// Formats a time as the UTC date and time EPUB metadata expects (e.g.
+/// `2023-01-31T12:00:00Z`).
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date of a day since the Unix epoch, after Howard Hinnant's
+    // `civil_from_days`
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Appends the section of a chapter to its book's document.
+///
+/// Words are grouped in their paragraphs, with an anchor at each verse
+/// (e.g. `c3v16`), and the words supplied by the translators are in
+/// italics.
+fn write_chapter(html: &mut String, chapter: i32, words: &[Word]) {
+    let _ = writeln!(
+        html,
+        "<section id=\"c{0}\" epub:type=\"chapter\">\n<h2>Chapter {0}</h2>",
+        chapter
+    );
+
+    for (i, word) in words.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| &words[i]);
+        let next = words.get(i + 1);
+
+        match previous {
+            Some(p) if p.paragraph == word.paragraph => html.push(' '),
+            Some(_) => html.push_str("</p>\n<p>"),
+            None => html.push_str("<p>"),
+        }
+        if previous.is_none_or(|p| p.verse != word.verse) {
+            let _ = write!(
+                html,
+                "<sup class=\"verse\" id=\"c{0}v{1}\">{1}</sup> ",
+                chapter, word.verse
+            );
+        }
+
+        if word.open_parentheses {
+            html.push('(');
+        }
+        // Runs of italic words share a tag, within a verse and paragraph
+        let same_run = |a: &Word, b: &Word| {
+            a.italic && b.italic && a.verse == b.verse && a.paragraph == b.paragraph
+        };
+        if word.italic && !previous.is_some_and(|p| same_run(p, word)) {
+            html.push_str("<i>");
+        }
+        html.push_str(&escape(&word.word));
+        if word.italic && !next.is_some_and(|n| same_run(word, n)) {
+            html.push_str("</i>");
+        }
+        if let Some(ref punctuation) = word.punctuation {
+            html.push_str(&escape(punctuation));
+        }
+        if word.close_parentheses {
+            html.push(')');
+        }
+    }
+    if !words.is_empty() {
+        html.push_str("</p>\n");
+    }
+    html.push_str("</section>\n");
+}
+
+/// Navigation document, listing every book and its chapters.
+fn navigation(range: &Range) -> String {
+    let mut html = head(&range.title);
+    let _ = writeln!(
+        html,
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>",
+        escape(&range.title)
+    );
+    for part in &range.books {
+        let document = document(&part.book);
+        let _ = write!(
+            html,
+            "<li><a href=\"{}\">{}</a>\n<ol>",
+            document,
+            escape(&part.book.name)
+        );
+        for chapter in &part.chapters {
+            let _ = write!(
+                html,
+                "<li><a href=\"{0}#c{1}\">{1}</a></li>",
+                document, chapter.chapter
+            );
+        }
+        html.push_str("</ol></li>\n");
+    }
+    html.push_str("</ol>\n</nav>\n");
+
+    if let Some(first) = range.books.first() {
+        let _ = writeln!(
+            html,
+            "<nav epub:type=\"landmarks\" hidden=\"\">\n<ol>\n\
+             <li><a epub:type=\"toc\" href=\"#toc\">Contents</a></li>\n\
+             <li><a epub:type=\"bodymatter\" href=\"{}\">Start</a></li>\n</ol>\n</nav>",
+            document(&first.book)
+        );
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Package document, with the metadata, the files and their reading
+/// order.
+fn package(range: &Range, modified: SystemTime) -> String {
+    let mut items = String::new();
+    let mut spine = String::new();
+    for part in &range.books {
+        let document = document(&part.book);
+        let id = document.trim_end_matches(".xhtml");
+        let _ = writeln!(
+            items,
+            "    <item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            id, document
+        );
+        let _ = writeln!(spine, "    <itemref idref=\"{}\"/>", id);
+    }
+
+    format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">urn:biblers:{translation}:{stem}</dc:identifier>
+    <dc:title id="title">{title}</dc:title>
+    <meta refines="#title" property="title-type">main</meta>
+    <dc:title id="translation">{translation_name}</dc:title>
+    <meta refines="#translation" property="title-type">edition</meta>
+    <dc:language>{language}</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="style" href="style.css" media-type="text/css"/>
+{items}  </manifest>
+  <spine>
+    <itemref idref="nav"/>
+{spine}  </spine>
+</package>
+"##,
+        language = LANGUAGE,
+        translation = TRANSLATION,
+        stem = range.file_stem(),
+        title = escape(&range.title),
+        translation_name = TRANSLATION_NAME,
+        modified = timestamp(modified),
+        items = items,
+        spine = spine,
+    )
+}
+
+/// Writes an EPUB 3 of a range of the Bible.
+///
+/// Every book is a document, with a section for each chapter and an
+/// anchor at each verse, and the navigation document lists the books
+/// and their chapters. `modified` is when the publication was made.
+pub async fn write<W: Write + Seek>(
+    drill: &dyn SwordDrillable,
+    range: &Range,
+    modified: SystemTime,
+    out: W,
+) -> Result<W, ExportError> {
+    let mut zip = ZipWriter::new(out);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // Readers recognize the format by this first, uncompressed file
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(MEDIA_TYPE.as_bytes())?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/style.css", deflated)?;
+    zip.write_all(STYLE.as_bytes())?;
+
+    for part in &range.books {
+        let mut html = head(&part.book.name);
+        let _ = writeln!(html, "<h1>{}</h1>", escape(&part.book.name));
+        for chapter in &part.chapters {
+            let (_, words) = drill.words(chapter).await?;
+            write_chapter(&mut html, chapter.chapter, &words);
+        }
+        html.push_str("</body>\n</html>\n");
+
+        zip.start_file(format!("OEBPS/{}", document(&part.book)), deflated)?;
+        zip.write_all(html.as_bytes())?;
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(navigation(range).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package(range, modified).as_bytes())?;
+
+    Ok(zip.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+    use std::time::Duration;
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::test::TestSwordDrill;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            "2000-02-29T12:34:56Z"
+        );
+    }
+
+    #[actix_web::test]
+    async fn epub() {
+        let drill = TestSwordDrill;
+        let mut range = Range::find(&drill, None).await.unwrap();
+        range.books[0].chapters.truncate(2);
+
+        let out = write(&drill, &range, UNIX_EPOCH, Cursor::new(Vec::new()))
+            .await
+            .unwrap();
+        let mut epub = ZipArchive::new(out).unwrap();
+        let mut file = |name: &str| {
+            let mut contents = String::new();
+            epub.by_name(name)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+
+        assert_eq!(file("mimetype"), MEDIA_TYPE);
+        assert!(file("OEBPS/content.opf")
+            .contains("<dc:title id=\"translation\">King James Version</dc:title>"));
+        assert!(file("OEBPS/nav.xhtml").contains("<a href=\"book-19.xhtml#c2\">2</a>"));
+        assert!(file("OEBPS/book-19.xhtml").contains(
+            "<p><sup class=\"verse\" id=\"c2v105\">105</sup> NUN. Thy word <i>is</i> a lamp \
+             unto my feet, and a light unto my path.</p>"
+        ));
+
+        let first = epub.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+    }
+}
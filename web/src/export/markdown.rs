@@ -0,0 +1,228 @@
+//! Markdown notes of the Bible for Obsidian-style vaults.
+//!
+//! Notes link to each other by name (e.g. `[[John 3]]`), and every verse
+//! is a block that can be linked to (e.g. `[[John 3#^v16]]`).
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use db::models::{Book, Testament, Verse};
+use db::{SwordDrillable, VerseFormat};
+
+use crate::cache::TRANSLATION;
+use crate::export::{BookRange, ExportError, Range};
+
+/// Way the notes of a range are split.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Split {
+    /// A note per chapter (e.g. `John/John 3.md`), with an index per book
+    #[default]
+    Chapter,
+    /// A note per book (e.g. `John.md`), with a section per chapter
+    Book,
+}
+
+/// Gets a note name from a title, leaving out the characters that can't
+/// be in one.
+fn note_name(title: &str) -> String {
+    title.replace([':', '/', '\\', '#', '^', '[', ']', '|'], ".")
+}
+
+/// YAML front matter of a note.
+fn front_matter(book: &Book, chapter: Option<i32>) -> String {
+    let testament = match book.testament {
+        Testament::Old => "Old",
+        Testament::New => "New",
+    };
+    let mut yaml = format!("---\nbook: {}\n", book.name);
+    match chapter {
+        Some(chapter) => {
+            let _ = writeln!(yaml, "chapter: {}", chapter);
+        }
+        None => {
+            let _ = writeln!(yaml, "chapters: {}", book.chapter_count);
+        }
+    }
+    let _ = write!(
+        yaml,
+        "testament: {}\ntranslation: {}\n---\n\n",
+        testament,
+        TRANSLATION.to_uppercase()
+    );
+    yaml
+}
+
+/// Links to a chapter, labeled with its name.
+fn chapter_link(book: &Book, chapter: i32, split: Split, label: &str) -> String {
+    match split {
+        Split::Chapter => format!("[[{} {}|{}]]", book.name, chapter, label),
+        Split::Book => format!("[[{}#Chapter {}|{}]]", book.name, chapter, label),
+    }
+}
+
+/// Line of links around a chapter, like the links of the verses pages:
+/// the range, the book, and the previous and next chapters.
+fn navigation(range: &Range, book: &Book, chapter: i32, books: &[Book], split: Split) -> String {
+    let mut links = vec![
+        format!("[[{}]]", note_name(&range.title)),
+        format!("[[{}]]", book.name),
+    ];
+    let (previous, next) = book.adjacent_chapters(chapter, books);
+    if let Some((b, c)) = previous {
+        links.push(chapter_link(b, c, split, &format!("← {} {}", b.name, c)));
+    }
+    if let Some((b, c)) = next {
+        links.push(chapter_link(b, c, split, &format!("{} {} →", b.name, c)));
+    }
+    links.join(" · ")
+}
+
+/// Verses of a chapter as blocks, each with an ID (e.g. `^v16`, or
+/// `^c3v16` in a note of a whole book).
+fn verse_blocks(chapter: i32, verses: &[Verse], split: Split) -> String {
+    let mut markdown = String::new();
+    for verse in verses {
+        let id = match split {
+            Split::Chapter => format!("v{}", verse.verse),
+            Split::Book => format!("c{}v{}", chapter, verse.verse),
+        };
+        let _ = write!(markdown, "**{}** {} ^{}\n\n", verse.verse, verse.words, id);
+    }
+    markdown
+}
+
+/// Writes the notes of a book of a range.
+async fn write_book(
+    drill: &dyn SwordDrillable,
+    range: &Range,
+    part: &BookRange,
+    books: &[Book],
+    split: Split,
+    dir: &Path,
+) -> Result<(), ExportError> {
+    let book = &part.book;
+    let mut index = front_matter(book, None);
+    let mut sections = String::new();
+    let _ = writeln!(
+        index,
+        "# {}\n\n[[{}]]\n",
+        book.name,
+        note_name(&range.title)
+    );
+
+    for chapter in &part.chapters {
+        let (_, verses) = drill.verses(chapter, VerseFormat::PlainText).await?;
+        let nav = navigation(range, book, chapter.chapter, books, split);
+        let blocks = verse_blocks(chapter.chapter, &verses, split);
+
+        match split {
+            Split::Chapter => {
+                let name = format!("{} {}", book.name, chapter.chapter);
+                let _ = writeln!(index, "- [[{}]]", name);
+
+                let mut note = front_matter(book, Some(chapter.chapter));
+                let _ = write!(note, "# {}\n\n{}\n\n{}", chapter, nav, blocks);
+                fs::write(dir.join(&book.name).join(format!("{}.md", name)), note)?;
+            }
+            Split::Book => {
+                let label = format!("{} {}", book.name, chapter.chapter);
+                let _ = writeln!(
+                    index,
+                    "- {}",
+                    chapter_link(book, chapter.chapter, split, &label)
+                );
+                let _ = write!(
+                    sections,
+                    "## Chapter {}\n\n{}\n\n{}",
+                    chapter.chapter, nav, blocks
+                );
+            }
+        }
+    }
+    if !sections.is_empty() {
+        index.push('\n');
+        index.push_str(&sections);
+    }
+
+    let path = match split {
+        Split::Chapter => dir.join(&book.name).join(format!("{}.md", book.name)),
+        Split::Book => dir.join(format!("{}.md", book.name)),
+    };
+    fs::write(path, index)?;
+    Ok(())
+}
+
+/// Writes a range of the Bible as Markdown notes in a directory.
+///
+/// Next to the notes is an index of the range, named after its title,
+/// listing its books.
+pub async fn write(
+    drill: &dyn SwordDrillable,
+    range: &Range,
+    split: Split,
+    dir: &Path,
+) -> Result<(), ExportError> {
+    let books = drill.all_books().await?;
+    fs::create_dir_all(dir)?;
+
+    let mut index = format!(
+        "---\ntranslation: {}\n---\n\n# {}\n\n",
+        TRANSLATION.to_uppercase(),
+        range.title
+    );
+    for part in &range.books {
+        if split == Split::Chapter {
+            fs::create_dir_all(dir.join(&part.book.name))?;
+        }
+        write_book(drill, range, part, &books, split, dir).await?;
+        let _ = writeln!(index, "- [[{}]]", part.book.name);
+    }
+    fs::write(dir.join(format!("{}.md", note_name(&range.title))), index)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::test::TestSwordDrill;
+
+    #[actix_web::test]
+    async fn vault() {
+        let dir = env::temp_dir().join(format!("biblers-vault-{}", std::process::id()));
+        let drill = TestSwordDrill;
+        let mut range = Range::find(&drill, None).await.unwrap();
+        range.books[0].chapters.truncate(2);
+
+        write(&drill, &range, Split::Chapter, &dir).await.unwrap();
+        let note = fs::read_to_string(dir.join("Psalms/Psalms 2.md")).unwrap();
+        assert!(note.starts_with(
+            "---\nbook: Psalms\nchapter: 2\ntestament: Old\ntranslation: KJV\n---\n\n# Psalms 2\n"
+        ));
+        assert!(note.contains(
+            "[[The Holy Bible]] · [[Psalms]] · [[Psalms 1|← Psalms 1]] · [[Psalms 3|Psalms 3 →]]"
+        ));
+        assert!(note.contains(
+            "**105** NUN. Thy word is a lamp unto my feet, and a light unto my path. ^v105\n"
+        ));
+        let index = fs::read_to_string(dir.join("Psalms/Psalms.md")).unwrap();
+        assert!(index.ends_with("- [[Psalms 1]]\n- [[Psalms 2]]\n"));
+        assert!(fs::read_to_string(dir.join("The Holy Bible.md"))
+            .unwrap()
+            .contains("- [[Psalms]]"));
+
+        write(&drill, &range, Split::Book, &dir).await.unwrap();
+        let note = fs::read_to_string(dir.join("Psalms.md")).unwrap();
+        assert!(note.contains(
+            "## Chapter 2\n\n[[The Holy Bible]] · [[Psalms]] · [[Psalms#Chapter 1|← Psalms 1]]"
+        ));
+        assert!(note.contains("- [[Psalms#Chapter 2|Psalms 2]]\n\n## Chapter 1\n"));
+        assert!(note.contains(" ^c2v105\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Exports of the Bible, or parts of it, as files to read elsewhere.
+
+use std::io;
+
+use thiserror::Error;
+use zip::result::ZipError;
+
+use db::models::{Book, Reference};
+use db::{DbError, SwordDrillable};
+
+pub mod epub;
+pub mod markdown;
+pub mod text;
+
+/// Full name of the translation that is exported.
+pub const TRANSLATION_NAME: &str = "King James Version";
+
+/// Language of the exported text, as a BCP 47 tag.
+pub const LANGUAGE: &str = "en";
+
+/// Error of an export.
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("Could not write the export. Root cause: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl From<ZipError> for ExportError {
+    fn from(e: ZipError) -> Self {
+        Self::Io(e.into())
+    }
+}
+
+/// Book of a range, with the chapters of it in the range.
+#[derive(Clone, Debug)]
+pub struct BookRange {
+    pub book: Book,
+    /// Chapters to export, with the verses of each when only some of
+    /// them are.
+    pub chapters: Vec<Reference>,
+}
+
+impl BookRange {
+    /// Every chapter of a book.
+    fn whole(book: &Book) -> Self {
+        Self {
+            book: book.to_owned(),
+            chapters: (1..=book.chapter_count)
+                .map(|chapter| Reference {
+                    book: book.name.to_owned(),
+                    chapter,
+                    verses: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Part of the Bible to export, in canonical order.
+#[derive(Clone, Debug)]
+pub struct Range {
+    /// Title of the part (e.g. "Genesis–Deuteronomy").
+    pub title: String,
+    pub books: Vec<BookRange>,
+}
+
+impl Range {
+    /// Finds the part of the Bible a range names.
+    ///
+    /// A range is a book (`John`), the first and last of several books
+    /// (`Genesis-Deuteronomy`) or a passage (`John 3` or `John 3:16-18`).
+    /// Without a range, the whole Bible is exported.
+    pub async fn find(drill: &dyn SwordDrillable, range: Option<&str>) -> Result<Self, DbError> {
+        let books = drill.all_books().await?;
+        let range = match range.map(str::trim) {
+            None | Some("") => {
+                return Ok(Self {
+                    title: "The Holy Bible".to_string(),
+                    books: books.iter().map(BookRange::whole).collect(),
+                })
+            }
+            Some(range) => range,
+        };
+
+        let not_a_book = match drill.book(range).await {
+            Ok((book, _)) => {
+                return Ok(Self {
+                    title: book.name.to_owned(),
+                    books: vec![BookRange::whole(&book)],
+                })
+            }
+            Err(e) => e,
+        };
+        if let Some((first, last)) = range.split_once('-') {
+            if let Ok((first, _)) = drill.book(first.trim()).await {
+                let (last, _) = drill.book(last.trim()).await?;
+                if last.id < first.id {
+                    return Err(DbError::InvalidReference {
+                        reference: range.to_string(),
+                    });
+                }
+                return Ok(Self {
+                    title: format!("{}–{}", first.name, last.name),
+                    books: books
+                        .iter()
+                        .filter(|b| (first.id..=last.id).contains(&b.id))
+                        .map(BookRange::whole)
+                        .collect(),
+                });
+            }
+        }
+
+        // Something that is neither a passage nor books is most likely a
+        // misspelled book
+        let reference: Reference = match range.parse() {
+            Ok(reference) => reference,
+            Err(_) => return Err(not_a_book),
+        };
+        let (book, _) = drill.book(&reference.book).await?;
+        if reference.chapter < 1 || reference.chapter > book.chapter_count {
+            return Err(DbError::InvalidReference {
+                reference: range.to_string(),
+            });
+        }
+        let reference = Reference {
+            book: book.name.to_owned(),
+            ..reference
+        };
+        Ok(Self {
+            title: reference.to_string(),
+            books: vec![BookRange {
+                book,
+                chapters: vec![reference],
+            }],
+        })
+    }
+
+    /// Name for a file of the range, without its extension (e.g.
+    /// `genesis-deuteronomy`).
+    pub fn file_stem(&self) -> String {
+        self.title
+            .to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<&str>>()
+            .join("-")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestSwordDrill;
+
+    #[actix_web::test]
+    async fn ranges() {
+        let drill = TestSwordDrill;
+
+        let bible = Range::find(&drill, None).await.unwrap();
+        assert_eq!(bible.title, "The Holy Bible");
+        assert_eq!(bible.file_stem(), "the-holy-bible");
+        assert_eq!(bible.books[0].chapters.len(), 150);
+
+        let range = Range {
+            title: "Genesis–Deuteronomy".to_string(),
+            books: Vec::new(),
+        };
+        assert_eq!(range.file_stem(), "genesis-deuteronomy");
+    }
+}
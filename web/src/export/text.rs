@@ -0,0 +1,205 @@
+//! Full text of the Bible as a table of verses, for data work.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use clap::ValueEnum;
+use serde_derive::Serialize;
+use tokio::sync::mpsc;
+
+use db::models::Verse;
+use db::{DbError, SwordDrillable};
+
+use crate::format::csv_field;
+
+/// Size in bytes the chunks of an export grow to before they are sent.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Format of the table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TextFormat {
+    /// A verse per line, after its reference
+    #[default]
+    Txt,
+    /// Comma separated values, with a header
+    Csv,
+    /// Tab separated values, with a header
+    Tsv,
+    /// A JSON object per line
+    Jsonl,
+}
+
+/// Verse of a JSON Lines export.
+#[derive(Serialize)]
+struct Record<'a> {
+    book: &'a str,
+    chapter: i32,
+    verse: i32,
+    text: &'a str,
+}
+
+/// Replaces the characters that can't be in a TSV field with spaces.
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+impl TextFormat {
+    /// Gets the format of a file extension.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(extension))
+            .copied()
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Txt => "txt",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+            Self::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Txt => "text/plain; charset=utf-8",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Tsv => "text/tab-separated-values; charset=utf-8",
+            Self::Jsonl => "application/jsonl; charset=utf-8",
+        }
+    }
+
+    /// First line of the table, naming its columns.
+    fn header(self) -> &'static str {
+        match self {
+            Self::Csv => "book,chapter,verse,text\n",
+            Self::Tsv => "book\tchapter\tverse\ttext\n",
+            Self::Txt | Self::Jsonl => "",
+        }
+    }
+
+    /// Appends the line of a verse.
+    fn write(self, out: &mut String, book: &str, verse: &Verse) {
+        let (chapter, number, text) = (verse.chapter, verse.verse, &verse.words);
+        let _ = match self {
+            Self::Txt => writeln!(out, "{} {}:{} {}", book, chapter, number, text),
+            Self::Csv => writeln!(
+                out,
+                "{},{},{},{}",
+                csv_field(book),
+                chapter,
+                number,
+                csv_field(text)
+            ),
+            Self::Tsv => writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                tsv_field(book),
+                chapter,
+                number,
+                tsv_field(text)
+            ),
+            Self::Jsonl => {
+                let record = Record {
+                    book,
+                    chapter,
+                    verse: number,
+                    text,
+                };
+                writeln!(out, "{}", serde_json::to_string(&record).unwrap())
+            }
+        };
+    }
+}
+
+/// Export of every verse of the Bible, read in chunks as the verses are
+/// streamed from the database, so it is never all in memory.
+pub struct Chunks {
+    format: TextFormat,
+    /// Names of the books by ID.
+    books: HashMap<i32, String>,
+    verses: mpsc::Receiver<Result<Verse, DbError>>,
+    started: bool,
+}
+
+impl Chunks {
+    /// Starts streaming the verses to export.
+    pub async fn new(drill: &dyn SwordDrillable, format: TextFormat) -> Result<Self, DbError> {
+        let books = drill
+            .all_books()
+            .await?
+            .into_iter()
+            .map(|b| (b.id, b.name))
+            .collect();
+
+        Ok(Self {
+            format,
+            books,
+            verses: drill.stream_verses(),
+            started: false,
+        })
+    }
+
+    /// Gets the next chunk of the export, or `None` at its end.
+    pub async fn next(&mut self) -> Option<Result<String, DbError>> {
+        let mut chunk = String::with_capacity(CHUNK_SIZE + 1024);
+        if !self.started {
+            chunk.push_str(self.format.header());
+            self.started = true;
+        }
+
+        while chunk.len() < CHUNK_SIZE {
+            match self.verses.recv().await {
+                Some(Ok(verse)) => {
+                    let book = self.books.get(&verse.book).map_or("", String::as_str);
+                    self.format.write(&mut chunk, book, &verse);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestSwordDrill;
+
+    /// Reads a whole export.
+    async fn export(format: TextFormat) -> String {
+        let mut chunks = Chunks::new(&TestSwordDrill, format).await.unwrap();
+        let mut text = String::new();
+        while let Some(chunk) = chunks.next().await {
+            text.push_str(&chunk.unwrap());
+        }
+        text
+    }
+
+    #[actix_web::test]
+    async fn formats() {
+        assert_eq!(
+            export(TextFormat::Txt).await,
+            "Psalms 119:104 Verse 104, \"quoted\".\nPsalms 119:105 Verse 105, \"quoted\".\n"
+        );
+        assert_eq!(
+            export(TextFormat::Csv).await.lines().collect::<Vec<_>>(),
+            [
+                "book,chapter,verse,text",
+                "Psalms,119,104,\"Verse 104, \"\"quoted\"\".\"",
+                "Psalms,119,105,\"Verse 105, \"\"quoted\"\".\""
+            ]
+        );
+        assert!(export(TextFormat::Tsv)
+            .await
+            .starts_with("book\tchapter\tverse\ttext\nPsalms\t119\t104\tVerse 104"));
+        assert!(export(TextFormat::Jsonl).await.starts_with(
+            "{\"book\":\"Psalms\",\"chapter\":119,\"verse\":104,\"text\":\"Verse 104, \\\"quoted\\\".\"}\n"
+        ));
+
+        assert_eq!(TextFormat::from_extension("CSV"), Some(TextFormat::Csv));
+        assert_eq!(TextFormat::from_extension("epub"), None);
+    }
+}
@@ -0,0 +1,516 @@
+#![warn(clippy::all)]
+
+use std::error::Error;
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::middleware::Compress;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use futures_util::future;
+use handlebars::Handlebars;
+use listenfd::ListenFd;
+use log::{info, warn};
+use thiserror::Error;
+
+use db::models::Reference;
+use db::{
+    build_pool, connect, content_version, prefetch_books, run_migrations, DbError,
+    MemorySwordDrill, SwordDrill, SwordDrillable,
+};
+
+use crate::cache::{EpubCache, PageCache};
+use crate::config::{Backend, Command, Config};
+use crate::controllers::{api, download, health, pwa, sitemap, view, Representation};
+use crate::metrics::{InstrumentedSwordDrill, Metrics};
+use crate::middleware::{
+    ContentVersion, HttpCache, RateLimit, RequestLog, RequestMetrics, SecurityHeaders,
+};
+use crate::offline::Offline;
+use crate::responder::{RoutePaths, Site, BOOK_ROUTE, REFERENCE_ROUTE, SITEMAP_BOOK_ROUTE};
+use crate::tls::CertResolver;
+
+pub use crate::config::{Args, ConfigError};
+pub use crate::tls::TlsError;
+
+/// Longest time the workers are waited for to release the server data
+/// once stopped, so its database connections can be closed.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Represents the [server data](actix_web.web.Data.html) for the application.
+pub struct ServerData {
+    pub books: Vec<db::models::Book>,
+    pub drill: Box<dyn SwordDrillable>,
+    pub epubs: EpubCache,
+    pub metrics: Metrics,
+    pub offline: Offline,
+    pub pages: PageCache,
+    pub site: Site,
+    pub template: Handlebars<'static>,
+}
+
+/// Registers the [Handlebars](handlebars.handlebars.html) templates for the application.
+fn register_templates(dir: &Path, site: &Site) -> Result<Handlebars<'static>, Box<dyn Error>> {
+    let mut tpl = Handlebars::new();
+    tpl.set_strict_mode(true);
+    tpl.register_templates_directory(".hbs", dir)?;
+    error::register_error_pages(dir, site)?;
+
+    Ok(tpl)
+}
+
+/// Configures the routes of the metrics server.
+fn metrics_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("metrics").route(web::get().to(health::metrics)));
+}
+
+/// Configures the application's routes.
+///
+/// Full-text search and the reference API are limited per client with
+/// their own rate limits.
+fn routes(
+    cfg: &mut web::ServiceConfig,
+    static_dir: &Path,
+    http_cache: &HttpCache,
+    search_limit: &RateLimit,
+    api_limit: &RateLimit,
+) {
+    cfg.service(actix_files::Files::new("/static", static_dir).use_etag(true))
+        .service(web::resource("about").name("about").to(view::about))
+        .service(web::resource("healthz").route(web::get().to(health::healthz)))
+        .service(web::resource("readyz").route(web::get().to(health::readyz)))
+        .service(web::resource("sw.js").route(web::get().to(pwa::service_worker)))
+        .service(
+            web::resource("robots.txt")
+                .wrap(http_cache.clone())
+                .route(web::get().to(sitemap::robots)),
+        )
+        .service(
+            web::resource("sitemap.xml")
+                .name("sitemap")
+                .wrap(http_cache.clone())
+                .route(web::get().to(sitemap::index)),
+        )
+        .service(
+            web::resource("sitemap/pages.xml")
+                .name("sitemap_pages")
+                .wrap(http_cache.clone())
+                .route(web::get().to(sitemap::pages)),
+        )
+        .service(
+            web::resource(SITEMAP_BOOK_ROUTE)
+                .name("sitemap_book")
+                .wrap(http_cache.clone())
+                .route(web::get().to(sitemap::book)),
+        )
+        .service(
+            web::resource("/")
+                .name("bible")
+                .wrap(http_cache.clone())
+                .route(web::get().to(view::all_books)),
+        )
+        .service(
+            web::resource("search")
+                .name("search")
+                .wrap(search_limit.clone())
+                .route(web::get().to(view::search)),
+        )
+        .service(
+            web::resource(BOOK_ROUTE)
+                .name("book")
+                .wrap(http_cache.clone())
+                .route(web::get().to(view::book)),
+        )
+        .service(
+            web::resource(REFERENCE_ROUTE)
+                .name("reference")
+                .wrap(http_cache.clone())
+                .route(web::get().to(view::reference)),
+        )
+        .service(
+            web::resource("api/search")
+                .name("api_search")
+                .wrap(http_cache.clone())
+                .wrap(search_limit.clone())
+                .route(web::get().to(api::search)),
+        )
+        .service(
+            web::resource("api/{reference}.json")
+                .name("api_reference")
+                .wrap(http_cache.clone())
+                .wrap(api_limit.clone())
+                .route(web::get().to(api::reference)),
+        )
+        .service(
+            web::resource("api/bundle/{translation}.json")
+                .name("bundle")
+                .wrap(http_cache.clone())
+                .wrap(api_limit.clone())
+                .route(web::get().to(pwa::bundle)),
+        )
+        .service(
+            web::resource("api/export/{translation}.epub")
+                .name("export_epub")
+                .wrap(http_cache.clone())
+                .wrap(api_limit.clone())
+                .route(web::get().to(download::epub)),
+        )
+        .service(
+            web::resource("api/export/{translation}.{format:txt|csv|tsv|jsonl}")
+                .name("export_text")
+                .wrap(http_cache.clone())
+                .wrap(api_limit.clone())
+                .route(web::get().to(download::text)),
+        )
+        .default_service(web::route().to(HttpResponse::NotFound));
+}
+
+/// Renders reference pages into the page cache.
+///
+/// `references` is either `all` for every chapter in the Bible, or a
+/// comma separated list of references (e.g. `John 3,Psalms 23`). The
+/// pages are rendered by the same function as the reference handler, for
+/// the configured site.
+async fn warm_up(app_data: &ServerData, references: &str) {
+    let references: Vec<Reference> = if references.trim() == "all" {
+        app_data
+            .books
+            .iter()
+            .flat_map(|b| {
+                (1..=b.chapter_count).map(move |chapter| Reference {
+                    book: b.name.to_owned(),
+                    chapter,
+                    verses: None,
+                })
+            })
+            .collect()
+    } else {
+        references
+            .split(',')
+            .filter_map(|r| r.trim().parse().ok())
+            .collect()
+    };
+
+    let urls = RoutePaths::new();
+    for reference in references {
+        let name = reference.to_string();
+        let page = view::reference_page(
+            app_data,
+            reference,
+            Representation::Html,
+            &app_data.site,
+            &urls,
+        );
+        match page.await {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!("Could not warm up {}: it has no verses", name),
+            Err(e) => warn!("Could not warm up {}: {}", name, e),
+        }
+    }
+
+    info!(
+        "Page cache warmed up with {} pages in {} bytes (hits: {}, misses: {})",
+        app_data.pages.len(),
+        app_data.pages.bytes(),
+        app_data.pages.hits(),
+        app_data.pages.misses()
+    );
+}
+
+/// Takes the TCP sockets passed by systemd socket activation, if any.
+///
+/// The sockets stay open while the server restarts, so connections made
+/// in the meantime wait for the new process instead of being refused.
+/// Only TCP sockets can be taken over, as actix removes the files of Unix
+/// sockets when shutting down.
+fn inherited_listeners() -> io::Result<Vec<TcpListener>> {
+    let mut fds = ListenFd::from_env();
+    (0..fds.len())
+        .filter_map(|i| fds.take_tcp_listener(i).transpose())
+        .collect()
+}
+
+/// Error keeping the web server from running.
+#[derive(Debug, Error)]
+pub enum RunError {
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("TLS error: {0}")]
+    Tls(#[from] TlsError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+
+    #[error("Template error: {0}")]
+    Template(String),
+
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+/// Runs the web server with its command line arguments until it is shut
+/// down, or the command given instead.
+pub fn run(mut args: Args) -> Result<(), RunError> {
+    let command = args.take_command();
+    // Load and validate the configuration
+    let config = Config::from_args(args)?;
+
+    let system = actix_web::rt::System::new();
+    match command {
+        Some(Command::Build { out }) => system.block_on(build(config, &out)),
+        None => system.block_on(serve(config)),
+    }
+}
+
+/// Versions the generated pages by database content and templates.
+fn content_version_of(config: &Config) -> Result<ContentVersion, RunError> {
+    Ok(ContentVersion::of_templates(
+        &content_version(&mut connect(&config.database_url)?)?,
+        &config.template_dir,
+    )?)
+}
+
+/// Builds the data shared by the workers, serving the Bible with the
+/// configured backend.
+fn server_data(
+    config: &Config,
+    metrics: &Metrics,
+    version: &ContentVersion,
+) -> Result<web::Data<ServerData>, RunError> {
+    // Select the backend and build its database connection pool
+    let url = &config.database_url;
+    let pool = build_pool(url, config.pool_size)?;
+    let drill: Box<dyn SwordDrillable> = match config.backend {
+        Backend::Sqlite => Box::new(SwordDrill::new(pool)),
+        // Fail to start rather than serve from another backend than asked
+        Backend::Memory => Box::new(MemorySwordDrill::load(pool).map_err(|e| {
+            io::Error::other(format!("Could not load the Bible into memory: {}", e))
+        })?),
+    };
+
+    Ok(web::Data::new(ServerData {
+        // Time every lookup
+        drill: Box::new(InstrumentedSwordDrill::new(drill, metrics)),
+        epubs: EpubCache::new(version),
+        metrics: metrics.clone(),
+        // Hash the static files the service worker precaches
+        offline: Offline::new(&config.static_dir, version)?,
+        // Preload book data with a non-pooled connection
+        books: prefetch_books(&mut connect(url)?)?,
+        // Build the rendered page cache
+        pages: PageCache::new(config.page_cache_mb * 1024 * 1024, &config.site),
+        // Build handlebars registry
+        template: register_templates(&config.template_dir, &config.site)
+            .map_err(|e| RunError::Template(e.to_string()))?,
+        site: config.site.clone(),
+    }))
+}
+
+/// Renders the site into a directory to host as static files.
+async fn build(mut config: Config, out: &Path) -> Result<(), RunError> {
+    logging::init(&config.log_level);
+
+    // Without requests to take it from, pages link to the configured site,
+    // and each page is only rendered once
+    config.site.from_request = false;
+    config.page_cache_mb = 0;
+    let version = content_version_of(&config)?;
+    let app_data = server_data(&config, &Metrics::new(), &version)?;
+
+    static_site::build(app_data, &config, out).await?;
+    Ok(())
+}
+
+/// Drops the server data once the stopped workers have released it,
+/// which closes the database connection pool, or gives up after a
+/// timeout as a request can outlive the shutdown timeout.
+async fn release(data: web::Data<ServerData>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut data = data.into_inner();
+    loop {
+        match Arc::try_unwrap(data) {
+            Ok(data) => {
+                drop(data);
+                return true;
+            }
+            Err(shared) if Instant::now() < deadline => {
+                data = shared;
+                actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+            }
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Serves the application with a validated configuration.
+async fn serve(config: Config) -> Result<(), RunError> {
+    // Set up logging
+    logging::init(&config.log_level);
+
+    // Set up sentry
+    info!(
+        "Sentry client initialized with DSN: '{}'",
+        config.sentry_dsn.clone().unwrap_or_default()
+    );
+    let sentry = sentry::init((config.sentry_dsn.clone(), sentry::ClientOptions::default()));
+
+    // Run DB migrations for a new SQLite database
+    let url = &config.database_url;
+    run_migrations(&mut connect(url)?)?;
+
+    // Version the generated pages by database content and templates
+    let version = content_version_of(&config)?;
+
+    let metrics = Metrics::new();
+    let app_data = server_data(&config, &metrics, &version)?;
+    info!(
+        "Serving {} with the {:?} backend",
+        config.site.url, config.backend
+    );
+
+    // Render pages into the page cache before serving requests
+    if let Some(ref warm) = config.page_cache_warm {
+        warm_up(&app_data, warm).await;
+    }
+
+    // Rate limits are shared by the workers
+    let limits = &config.rate_limits;
+    let search_limit = RateLimit::new(limits.search, limits);
+    let api_limit = RateLimit::new(limits.api, limits);
+
+    // Kept to close the connection pool once the workers are stopped
+    let server_data = app_data.clone();
+    let server_config = config.clone();
+    let mut server = HttpServer::new(move || {
+        let http_cache = HttpCache::new(version.clone(), server_config.cache_max_age);
+
+        // Wire up the application
+        App::new()
+            .wrap(sentry_actix::Sentry::new())
+            .wrap(SecurityHeaders)
+            .wrap(Compress::default())
+            .wrap(RequestLog)
+            .wrap(RequestMetrics::new(metrics.clone()))
+            .app_data(app_data.clone())
+            .configure(|cfg| {
+                routes(
+                    cfg,
+                    &server_config.static_dir,
+                    &http_cache,
+                    &search_limit,
+                    &api_limit,
+                )
+            })
+    });
+    if let Some(workers) = config.workers {
+        server = server.workers(workers);
+    }
+    // Stop accepting connections on SIGTERM, and give the requests in
+    // flight time to finish
+    server = server.shutdown_timeout(config.shutdown_timeout);
+
+    // Terminate TLS with a certificate reloaded when renewed
+    let tls_config = match config.tls {
+        Some(ref tls) => {
+            let resolver = Arc::new(CertResolver::load(&tls.cert, &tls.key)?);
+            resolver.watch();
+            Some(tls::server_config(resolver))
+        }
+        None => None,
+    };
+    let listeners = inherited_listeners()?;
+    let inherited = !listeners.is_empty();
+    for listener in listeners {
+        let addr = listener.local_addr()?;
+        match tls_config {
+            Some(ref tls_config) => {
+                info!("Listening on inherited {} with TLS", addr);
+                server = server.listen_rustls(listener, tls_config.clone())?;
+            }
+            None => {
+                info!("Listening on inherited {}", addr);
+                server = server.listen(listener)?;
+            }
+        }
+    }
+    for addr in config.bind.iter().filter(|_| !inherited) {
+        match tls_config {
+            Some(ref tls_config) => {
+                info!("Listening on {} with TLS", addr);
+                server = server.bind_rustls(addr, tls_config.clone())?;
+            }
+            None => {
+                info!("Listening on {}", addr);
+                server = server.bind(addr)?;
+            }
+        }
+    }
+    #[cfg(unix)]
+    if let Some(ref path) = config.unix_socket {
+        info!("Listening on {}", path.display());
+        server = server.bind_uds(path)?;
+    }
+
+    // Redirect plain HTTP to the first HTTPS address
+    let mut servers = Vec::new();
+    let redirect_bind = config.tls.as_ref().and_then(|t| t.redirect_bind.as_ref());
+    if let (Some(addr), Some(https_addr)) = (redirect_bind, server.addrs().first()) {
+        let https_port = web::Data::new(https_addr.port());
+        info!("Redirecting {} to HTTPS", addr);
+        let redirect = HttpServer::new(move || {
+            App::new()
+                .app_data(https_port.clone())
+                .default_service(web::to(tls::redirect_to_https))
+        })
+        .workers(1)
+        .bind(addr)?
+        .run();
+        servers.push(redirect);
+    }
+
+    // Serve metrics apart from the site, so they aren't public
+    if let Some(ref addr) = config.metrics_bind {
+        info!("Serving metrics on {}", addr);
+        let metrics_data = server_data.clone();
+        let metrics_server = HttpServer::new(move || {
+            App::new()
+                .app_data(metrics_data.clone())
+                .configure(metrics_routes)
+        })
+        .workers(1)
+        .bind(addr)?
+        .run();
+        servers.push(metrics_server);
+    }
+
+    servers.push(server.run());
+    future::try_join_all(servers).await?;
+
+    // Close the database connections, and send the errors Sentry hasn't
+    // yet before exiting
+    info!("Server stopped");
+    if !release(server_data, RELEASE_TIMEOUT).await {
+        warn!("Exiting with database connections still in use");
+    }
+    sentry.close(Some(Duration::from_secs(2)));
+    Ok(())
+}
+
+mod cache;
+mod config;
+mod controllers;
+mod error;
+pub mod export;
+pub mod format;
+mod logging;
+mod metrics;
+mod middleware;
+mod offline;
+mod responder;
+mod static_site;
+#[cfg(test)]
+mod test;
+mod tls;
@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::Write;
+
+use log::kv::{self, Key, Value as KvValue, Visitor};
+use log::Record;
+use serde_json::{Map, Value};
+
+tokio::task_local! {
+    /// Context of the request being served by the current task.
+    static REQUEST: RequestContext;
+}
+
+/// Identifier and fields of a request, added to everything it logs.
+struct RequestContext {
+    id: String,
+    fields: RefCell<Map<String, Value>>,
+}
+
+/// Runs a request's future with its ID, so every line logged while serving
+/// it, including database and template errors, can be traced back to it.
+pub async fn scope<F: Future>(id: String, f: F) -> F::Output {
+    let context = RequestContext {
+        id,
+        fields: RefCell::new(Map::new()),
+    };
+    REQUEST.scope(context, f).await
+}
+
+/// Records a field (e.g. the parsed reference) of the current request.
+///
+/// Does nothing outside of a request.
+pub fn record(key: &str, value: impl Into<Value>) {
+    let _ = REQUEST.try_with(|context| {
+        context
+            .fields
+            .borrow_mut()
+            .insert(key.to_string(), value.into());
+    });
+}
+
+/// Sets up logging of JSON lines on stderr.
+///
+/// The filter uses the `RUST_LOG` syntax (e.g. `info` or `warn,web=debug`).
+pub fn init(filters: &str) {
+    env_logger::Builder::new()
+        .parse_filters(filters)
+        .format(|buf, record| {
+            let mut entry = entry(record);
+            entry.insert(
+                "timestamp".into(),
+                buf.timestamp_millis().to_string().into(),
+            );
+            writeln!(buf, "{}", Value::Object(entry))
+        })
+        .init();
+}
+
+/// Builds the JSON object of a log record, without its timestamp.
+fn entry(record: &Record) -> Map<String, Value> {
+    let mut entry = Map::new();
+    entry.insert("level".into(), record.level().as_str().into());
+    entry.insert("target".into(), record.target().into());
+    entry.insert("message".into(), record.args().to_string().into());
+
+    let _ = REQUEST.try_with(|context| {
+        entry.insert("request_id".into(), context.id.clone().into());
+        for (key, value) in context.fields.borrow().iter() {
+            entry.insert(key.clone(), value.clone());
+        }
+    });
+    let _ = record.key_values().visit(&mut FieldVisitor(&mut entry));
+
+    entry
+}
+
+/// Copies the key-values of a log record into its JSON object.
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> Visitor<'kvs> for FieldVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: KvValue<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            n.into()
+        } else if let Some(n) = value.to_i64() {
+            n.into()
+        } else if let Some(b) = value.to_bool() {
+            b.into()
+        } else if let Some(n) = value.to_f64() {
+            n.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+    use serde_json::json;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn entry() {
+        let kvs: [(&str, &dyn kv::ToValue); 2] = [("status", &404), ("path", &"/Jn/3")];
+        let entry = |kvs: &[(&str, &dyn kv::ToValue)]| {
+            super::entry(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("web")
+                    .args(format_args!("Not found"))
+                    .key_values(&kvs)
+                    .build(),
+            )
+        };
+
+        assert_eq!(
+            Value::Object(entry(&kvs)),
+            json!({
+                "level": "WARN",
+                "target": "web",
+                "message": "Not found",
+                "path": "/Jn/3",
+                "status": 404,
+            })
+        );
+
+        let scoped = scope("abc".to_string(), async {
+            record("query", "light");
+            entry(&[])
+        })
+        .await;
+        assert_eq!(scoped["request_id"], "abc");
+        assert_eq!(scoped["query"], "light");
+    }
+}
@@ -1,13 +0,0 @@
-#![macro_use]
-
-macro_rules! title_format {
-    () => {
-        "Bible.rs | {}"
-    };
-}
-
-macro_rules! url_format {
-    () => {
-        "https://bible.rs{}"
-    };
-}
@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use tokio::sync::mpsc;
+
+use db::models::{Book, Reference, Verse, VerseFTS, Word};
+use db::{DbError, PoolStats, SwordDrillable, VerseFormat};
+
+/// Prefix of every metric's name.
+const NAMESPACE: &str = "biblers";
+
+/// Prometheus metrics of the application.
+///
+/// Metrics are registered in their own registry, so several applications
+/// (e.g. in tests) never share counts.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Served requests by route, method and status.
+    pub requests: IntCounterVec,
+    /// Time taken to serve requests by route.
+    pub request_duration: HistogramVec,
+    /// Time taken by each [SwordDrillable](db::SwordDrillable) method.
+    pub query_duration: HistogramVec,
+    pool_connections: IntGauge,
+    pool_idle_connections: IntGauge,
+    pool_max_size: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None).unwrap();
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests served."),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to serve HTTP requests.",
+            ),
+            &["route"],
+        )
+        .unwrap();
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_query_duration_seconds",
+                "Time taken to look up the Bible, by method.",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Number of open database connections.",
+        )
+        .unwrap();
+        let pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Number of idle database connections.",
+        )
+        .unwrap();
+        let pool_max_size =
+            IntGauge::new("db_pool_max_size", "Max number of database connections.").unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(query_duration.clone())).unwrap();
+        registry
+            .register(Box::new(pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_idle_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(pool_max_size.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            query_duration,
+            pool_connections,
+            pool_idle_connections,
+            pool_max_size,
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format, with the
+    /// current statistics of the connection pool.
+    pub fn render(&self, pool_stats: Option<PoolStats>) -> String {
+        if let Some(stats) = pool_stats {
+            self.pool_connections.set(stats.connections.into());
+            self.pool_idle_connections
+                .set(stats.idle_connections.into());
+            self.pool_max_size.set(stats.max_size.into());
+        }
+
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implementation of [SwordDrillable](db::SwordDrillable) that times
+/// each lookup of another implementation.
+pub struct InstrumentedSwordDrill {
+    drill: Box<dyn SwordDrillable>,
+    durations: HistogramVec,
+}
+
+impl InstrumentedSwordDrill {
+    pub fn new(drill: Box<dyn SwordDrillable>, metrics: &Metrics) -> Self {
+        Self {
+            drill,
+            durations: metrics.query_duration.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwordDrillable for InstrumentedSwordDrill {
+    async fn verses(
+        &self,
+        reference: &Reference,
+        format: VerseFormat,
+    ) -> Result<(Book, Vec<Verse>), DbError> {
+        let _timer = self.durations.with_label_values(&["verses"]).start_timer();
+        self.drill.verses(reference, format).await
+    }
+
+    async fn words(&self, reference: &Reference) -> Result<(Book, Vec<Word>), DbError> {
+        let _timer = self.durations.with_label_values(&["words"]).start_timer();
+        self.drill.words(reference).await
+    }
+
+    async fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+        let _timer = self.durations.with_label_values(&["book"]).start_timer();
+        self.drill.book(book_name).await
+    }
+
+    async fn all_books(&self) -> Result<Vec<Book>, DbError> {
+        let _timer = self
+            .durations
+            .with_label_values(&["all_books"])
+            .start_timer();
+        self.drill.all_books().await
+    }
+
+    async fn verse_ids(&self) -> Result<Vec<i32>, DbError> {
+        let _timer = self
+            .durations
+            .with_label_values(&["verse_ids"])
+            .start_timer();
+        self.drill.verse_ids().await
+    }
+
+    async fn verse(&self, id: i32) -> Result<(Book, Verse), DbError> {
+        let _timer = self.durations.with_label_values(&["verse"]).start_timer();
+        self.drill.verse(id).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<(VerseFTS, Book)>, DbError> {
+        let _timer = self.durations.with_label_values(&["search"]).start_timer();
+        self.drill.search(query).await
+    }
+
+    async fn ready(&self) -> Result<(), DbError> {
+        self.drill.ready().await
+    }
+
+    fn stream_verses(&self) -> mpsc::Receiver<Result<Verse, DbError>> {
+        self.drill.stream_verses()
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.drill.pool_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestSwordDrill;
+
+    #[actix_web::test]
+    async fn query_duration() {
+        let metrics = Metrics::new();
+        let drill = InstrumentedSwordDrill::new(Box::new(TestSwordDrill), &metrics);
+
+        drill.book("Psalms").await.unwrap();
+        drill.book("Psalms").await.unwrap();
+        drill.search("light").await.unwrap();
+
+        let text = metrics.render(Some(PoolStats {
+            connections: 2,
+            idle_connections: 1,
+            max_size: 15,
+        }));
+        assert!(text.contains("biblers_db_query_duration_seconds_count{method=\"book\"} 2"));
+        assert!(text.contains("biblers_db_query_duration_seconds_count{method=\"search\"} 1"));
+        assert!(text.contains("biblers_db_pool_idle_connections 1"));
+        assert!(text.contains("biblers_db_pool_max_size 15"));
+    }
+}
@@ -0,0 +1,424 @@
+use std::fs;
+use std::future::{ready, Ready};
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{
+    self, CacheControl, CacheDirective, ETag, EntityTag, Header, HeaderName, HeaderValue, HttpDate,
+    IfModifiedSince, IfNoneMatch, LastModified, TryIntoHeaderPair,
+};
+use actix_web::http::Method;
+use actix_web::{Error, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use sha2::{Digest, Sha256};
+
+use crate::controllers::Representation;
+
+/// Time of a migration version made of its UTC date and time (e.g.
+/// `20190521055300`).
+fn migration_time(version: &str) -> Option<SystemTime> {
+    if version.len() != 14 || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| version[range].parse::<u64>().unwrap();
+    let (year, month, day) = (field(0..4), field(4..6), field(6..8));
+    let (hour, minute, second) = (field(8..10), field(10..12), field(12..14));
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the Unix epoch of a civil date, after Howard Hinnant's
+    // `days_from_civil`
+    let y = year - u64::from(month <= 2);
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Drops the fraction of a second of a time, which HTTP dates don't have.
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// First 64 bits of a SHA-256 digest, which are stable across builds.
+fn digest(hasher: Sha256) -> u64 {
+    let digest = hasher.finalize();
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Version of everything a generated response depends on.
+///
+/// The Bible text only changes when the database is migrated, and the
+/// markup only changes when the templates do, so responses can be
+/// validated with an ETag derived from both, and with a `Last-Modified`
+/// date from the latest migration or template change.
+#[derive(Clone, Debug)]
+pub struct ContentVersion {
+    tag: u64,
+    modified: SystemTime,
+}
+
+impl ContentVersion {
+    /// Creates a content version from the database content version and a
+    /// hash of the templates.
+    pub fn new(db_version: &str, template_hash: u64) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(db_version);
+        hasher.update(template_hash.to_be_bytes());
+
+        Self {
+            tag: digest(hasher),
+            modified: db_version
+                .split(',')
+                .filter_map(migration_time)
+                .max()
+                .unwrap_or(UNIX_EPOCH),
+        }
+    }
+
+    /// Creates the content version of a database and the templates in a
+    /// directory, dated by the latest of their changes.
+    pub fn of_templates(db_version: &str, dir: &Path) -> io::Result<Self> {
+        let (template_hash, templates_modified) = Self::hash_templates(dir)?;
+        let mut version = Self::new(db_version, template_hash);
+        version.modified = version.modified.max(whole_seconds(templates_modified));
+        Ok(version)
+    }
+
+    /// When the content last changed: the time of the latest migration or
+    /// template change, or the Unix epoch when neither can be told.
+    ///
+    /// Unlike the time the server started, this is the same on every
+    /// instance serving the same database and templates.
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    /// Identifier of the version, to name what depends on it with.
+    pub fn id(&self) -> String {
+        format!("{:016x}", self.tag)
+    }
+
+    /// Hashes the names and contents of the templates in a directory, and
+    /// gets when the latest of them was modified.
+    fn hash_templates(dir: &Path) -> io::Result<(u64, SystemTime)> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut hasher = Sha256::new();
+        let mut modified = UNIX_EPOCH;
+        for path in paths.iter().filter(|p| p.is_file()) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            hasher.update(name.as_bytes());
+            hasher.update(Sha256::digest(fs::read(path)?));
+            modified = modified.max(fs::metadata(path)?.modified()?);
+        }
+
+        Ok((digest(hasher), modified))
+    }
+
+    /// Generates the entity tag for a request.
+    ///
+    /// Each URL and negotiated representation gets its own tag. Tags are
+    /// weak, as a response is the same whichever content coding it is
+    /// sent with (e.g. a gzipped or plain bundle).
+    fn entity_tag(&self, req: &ServiceRequest) -> EntityTag {
+        let mut hasher = Sha256::new();
+        hasher.update(self.tag.to_be_bytes());
+        hasher.update(req.uri().path());
+        // Tell a missing query from an empty one
+        match req.uri().query() {
+            Some(query) => hasher.update(format!("?{}", query)),
+            None => hasher.update("\0"),
+        }
+        hasher.update([Representation::from_request(req.request()) as u8]);
+
+        EntityTag::new_weak(format!("{:016x}", digest(hasher)))
+    }
+}
+
+/// Middleware that adds validators and caching headers to generated
+/// responses, answering conditional requests with `304 Not Modified`.
+#[derive(Clone, Debug)]
+pub struct HttpCache {
+    max_age: u32,
+    version: Rc<ContentVersion>,
+}
+
+impl HttpCache {
+    /// Creates the middleware for the given content version, with a
+    /// `Cache-Control` max age in seconds.
+    pub fn new(version: ContentVersion, max_age: u32) -> Self {
+        Self {
+            max_age,
+            version: Rc::new(version),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HttpCache
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HttpCacheMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpCacheMiddleware {
+            max_age: self.max_age,
+            service: Rc::new(service),
+            version: self.version.clone(),
+        }))
+    }
+}
+
+pub struct HttpCacheMiddleware<S> {
+    max_age: u32,
+    service: Rc<S>,
+    version: Rc<ContentVersion>,
+}
+
+/// What a request's conditional headers match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Precondition {
+    /// No cached copy, or an outdated one.
+    None,
+    /// The cached copy has the current entity tag.
+    Tag,
+    /// Any cached copy (`*`), or one at least as recent as the content,
+    /// which is only fresh if the resource exists.
+    Found,
+}
+
+impl<S> HttpCacheMiddleware<S> {
+    /// Whether the client's cached copy is still fresh.
+    ///
+    /// `If-Modified-Since` is only looked at without `If-None-Match`, as
+    /// the tag tells more precisely.
+    fn precondition(&self, req: &ServiceRequest, etag: &EntityTag) -> Precondition {
+        if req.headers().contains_key(header::IF_NONE_MATCH) {
+            return match IfNoneMatch::parse(req) {
+                Ok(IfNoneMatch::Any) => Precondition::Found,
+                Ok(IfNoneMatch::Items(tags)) if tags.iter().any(|t| t.weak_eq(etag)) => {
+                    Precondition::Tag
+                }
+                _ => Precondition::None,
+            };
+        }
+        match IfModifiedSince::parse(req) {
+            Ok(IfModifiedSince(since)) if self.version.modified <= SystemTime::from(since) => {
+                Precondition::Found
+            }
+            _ => Precondition::None,
+        }
+    }
+
+    /// Headers set on both full and `304` responses.
+    fn headers(&self, etag: EntityTag) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = vec![
+            ETag(etag).try_into_pair().unwrap(),
+            CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(self.max_age),
+            ])
+            .try_into_pair()
+            .unwrap(),
+        ];
+        if self.version.modified > UNIX_EPOCH {
+            let modified = HttpDate::from(self.version.modified);
+            headers.push(LastModified(modified).try_into_pair().unwrap());
+        }
+        headers
+    }
+}
+
+/// Response telling the client its cached copy is fresh.
+///
+/// It varies like the full responses do, by the negotiated representation
+/// and content coding, so shared caches keep telling them apart.
+fn not_modified(headers: Vec<(HeaderName, HeaderValue)>) -> HttpResponse {
+    let mut res = HttpResponse::NotModified();
+    res.insert_header((header::VARY, "Accept, Accept-Encoding"));
+    for h in headers {
+        res.insert_header(h);
+    }
+    res.finish()
+}
+
+impl<S, B> Service<ServiceRequest> for HttpCacheMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(|res| res.map_into_left_body()) });
+        }
+
+        let etag = self.version.entity_tag(&req);
+        let headers = self.headers(etag.clone());
+
+        // Tags are only sent with successful responses, so a matching one
+        // means the client has this very response. Any copy, or a dated
+        // one, only matches once the handler has found the resource.
+        let precondition = self.precondition(&req, &etag);
+        if precondition == Precondition::Tag {
+            return Box::pin(ready(Ok(req
+                .into_response(not_modified(headers))
+                .map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if !res.status().is_success() {
+                return Ok(res.map_into_left_body());
+            }
+            if precondition == Precondition::Found {
+                let (req, _) = res.into_parts();
+                return Ok(ServiceResponse::new(req, not_modified(headers)).map_into_right_body());
+            }
+
+            for (name, value) in headers {
+                res.headers_mut().insert(name, value);
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    use super::*;
+
+    #[test]
+    fn modified() {
+        let version = ContentVersion::new("20180102030405,20190521055300", 3);
+        assert_eq!(
+            version.modified(),
+            UNIX_EPOCH + Duration::from_secs(1_558_417_980)
+        );
+        assert_eq!(ContentVersion::new("1,2", 3).modified(), UNIX_EPOCH);
+        assert_eq!(
+            ContentVersion::new("20191321000000", 3).modified(),
+            UNIX_EPOCH
+        );
+
+        // Changed templates date the content too
+        let version = ContentVersion::of_templates("1,2", Path::new("./templates")).unwrap();
+        assert!(version.modified() > UNIX_EPOCH);
+        assert_eq!(version.modified(), whole_seconds(version.modified()));
+    }
+
+    #[actix_web::test]
+    async fn conditional_requests() {
+        let srv = init_service(
+            App::new().service(
+                web::resource("/{book}/{chapter}")
+                    .wrap(HttpCache::new(
+                        ContentVersion::new("20190521055300", 3),
+                        86400,
+                    ))
+                    .to(|path: web::Path<(String, u32)>| async move {
+                        if path.0 == "John" {
+                            HttpResponse::Ok().finish()
+                        } else {
+                            HttpResponse::NotFound().finish()
+                        }
+                    }),
+            ),
+        )
+        .await;
+
+        // Full response with validators
+        let res = call_service(&srv, TestRequest::with_uri("/John/3").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=86400"
+        );
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        // The same across builds and instances
+        assert_eq!(etag, "W/\"93775ac75d91794d\"");
+        let modified = "Tue, 21 May 2019 05:53:00 GMT";
+        assert_eq!(res.headers().get(header::LAST_MODIFIED).unwrap(), modified);
+
+        // Matching ETag
+        let req = TestRequest::with_uri("/John/3")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), &etag);
+        assert_eq!(
+            res.headers().get(header::VARY).unwrap(),
+            "Accept, Accept-Encoding"
+        );
+
+        // Different representation
+        let req = TestRequest::with_uri("/John/3")
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .insert_header((header::ACCEPT, "application/json"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Any cached copy, of a page that exists or not
+        let req = TestRequest::with_uri("/John/3")
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        let req = TestRequest::with_uri("/Hezekiah/1")
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(res.headers().get(header::ETAG).is_none());
+
+        // Dated copies, as fresh as the content or older
+        let since = |uri: &str, date: &str| {
+            TestRequest::with_uri(uri)
+                .insert_header((header::IF_MODIFIED_SINCE, date.to_string()))
+                .to_request()
+        };
+        let res = call_service(&srv, since("/John/3", modified)).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get(header::LAST_MODIFIED).unwrap(), modified);
+        let res = call_service(&srv, since("/John/3", "Mon, 20 May 2019 05:53:00 GMT")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = call_service(&srv, since("/Hezekiah/1", modified)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
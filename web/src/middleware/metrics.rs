@@ -0,0 +1,156 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::metrics::Metrics;
+
+/// Route label of requests that didn't match a resource.
+const UNMATCHED: &str = "unmatched";
+
+/// Method label of requests with a non-standard method.
+const OTHER_METHOD: &str = "OTHER";
+
+/// Labels a request method, collapsing the methods HTTP doesn't define so
+/// that clients can't add labels at will.
+fn method_label(method: &Method) -> &str {
+    match *method {
+        Method::GET
+        | Method::HEAD
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::CONNECT
+        | Method::OPTIONS
+        | Method::TRACE
+        | Method::PATCH => method.as_str(),
+        _ => OTHER_METHOD,
+    }
+}
+
+/// Middleware that counts and times requests by route.
+///
+/// Routes are labelled with the resource name, falling back to the
+/// resource pattern for unnamed resources, so the number of labels stays
+/// bounded whatever the request paths are.
+#[derive(Clone)]
+pub struct RequestMetrics {
+    metrics: Rc<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Metrics) -> Self {
+        Self {
+            metrics: Rc::new(metrics),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            metrics: self.metrics.clone(),
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    metrics: Rc<Metrics>,
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let metrics = self.metrics.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            let req = res.request();
+            let route = req
+                .match_name()
+                .map(str::to_string)
+                .or_else(|| req.match_pattern())
+                .unwrap_or_else(|| UNMATCHED.to_string());
+            metrics
+                .requests
+                .with_label_values(&[&route, method_label(req.method()), res.status().as_str()])
+                .inc();
+            metrics
+                .request_duration
+                .with_label_values(&[&route])
+                .observe(start.elapsed().as_secs_f64());
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn request_metrics() {
+        let metrics = Metrics::new();
+        let srv = test::init_service(
+            App::new()
+                .wrap(RequestMetrics::new(metrics.clone()))
+                .service(web::resource("{book}").name("book").to(HttpResponse::Ok))
+                .service(web::resource("{book}/{chapter}").to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for uri in ["/John", "/Psalms", "/John/3", "/John/3/16"] {
+            test::call_service(&srv, test::TestRequest::with_uri(uri).to_request()).await;
+        }
+        let custom = Method::from_bytes(b"FROBNICATE").unwrap();
+        let req = test::TestRequest::with_uri("/John").method(custom);
+        test::call_service(&srv, req.to_request()).await;
+
+        let text = metrics.render(None);
+        assert!(text.contains(
+            "biblers_http_requests_total{method=\"GET\",route=\"book\",status=\"200\"} 2"
+        ));
+        assert!(text.contains(
+            "biblers_http_requests_total{method=\"GET\",route=\"/{book}/{chapter}\",status=\"200\"} 1"
+        ));
+        assert!(text.contains(
+            "biblers_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1"
+        ));
+        assert!(text.contains(
+            "biblers_http_requests_total{method=\"OTHER\",route=\"book\",status=\"200\"} 1"
+        ));
+        assert!(!text.contains("FROBNICATE"));
+        assert!(text.contains("biblers_http_request_duration_seconds_count{route=\"book\"} 3"));
+    }
+}
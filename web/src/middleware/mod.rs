@@ -0,0 +1,11 @@
+mod http_cache;
+mod metrics;
+mod rate_limit;
+mod request_log;
+mod security_headers;
+
+pub use self::http_cache::{ContentVersion, HttpCache};
+pub use self::metrics::RequestMetrics;
+pub use self::rate_limit::{Network, Quota, RateLimit, RateLimits};
+pub use self::request_log::RequestLog;
+pub use self::security_headers::SecurityHeaders;
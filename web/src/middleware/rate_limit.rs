@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName};
+use actix_web::{Error, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use log::warn;
+
+/// Number of tracked clients above which idle ones are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Shortest time between two sweeps of the idle clients, so a flood of
+/// new clients doesn't make every request go through all of them.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Address of the clients without one (e.g. on a Unix socket without a
+/// proxy header), which share a bucket.
+const UNKNOWN_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// Prefix length of the IPv6 networks limited as one client. Subscribers
+/// usually get at least a /64, so they could otherwise pick a new address
+/// for every request.
+const IPV6_CLIENT_PREFIX: u32 = 64;
+
+/// Rate of requests a client can make to a group of routes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    /// Requests allowed per minute, on average.
+    pub per_minute: u32,
+    /// Requests that can be made at once after being idle.
+    pub burst: u32,
+}
+
+impl Quota {
+    /// Tokens added to a client's bucket per second.
+    fn rate(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Network of client addresses, in CIDR notation (e.g. `10.0.0.0/8`) or
+/// as a single address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    /// Whether an address is in the network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let (net, addr, bits) = match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                (u32::from(net).into(), u32::from(addr).into(), 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => (u128::from(net), u128::from(addr), 128),
+            _ => return false,
+        };
+        let shift = bits - u32::from(self.prefix);
+        shift >= bits || (net >> shift) == (addr >> shift)
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not an IP address or network", s);
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr = addr.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Limits of the route groups, and how clients are told apart.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimits {
+    /// Clients that are never limited.
+    pub allow: Vec<Network>,
+    /// Quota of the JSON reference API.
+    pub api: Option<Quota>,
+    /// Header with the client's address set by a trusted proxy (e.g.
+    /// `X-Real-IP`). Of a list, the last address is used, which is the one
+    /// the proxy added. Without it, the peer address is used.
+    pub client_ip_header: Option<String>,
+    /// Quota of the full-text search page and API.
+    pub search: Option<Quota>,
+}
+
+/// Token bucket of a client.
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Buckets of the clients, with when the idle ones were last forgotten.
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    swept: Instant,
+}
+
+impl Buckets {
+    fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            swept: Instant::now(),
+        }
+    }
+}
+
+/// Buckets of the clients of a route group.
+struct Limiter {
+    allow: Vec<Network>,
+    buckets: Mutex<Buckets>,
+    client_ip_header: Option<HeaderName>,
+    quota: Quota,
+}
+
+impl Limiter {
+    /// Takes a token from a client's bucket, or gets how long the client
+    /// must wait for the next one.
+    fn check(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        if self.allow.iter().any(|n| n.contains(client)) {
+            return Ok(());
+        }
+
+        let capacity = f64::from(self.quota.burst);
+        let rate = self.quota.rate();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.clients.len() >= MAX_TRACKED_CLIENTS
+            && now.saturating_duration_since(buckets.swept) >= SWEEP_INTERVAL
+        {
+            // A full bucket is the same as no bucket at all
+            buckets.clients.retain(|_, b| {
+                b.tokens + now.saturating_duration_since(b.updated).as_secs_f64() * rate < capacity
+            });
+            buckets.swept = now;
+        }
+
+        let bucket = buckets.clients.entry(bucket_key(client)).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.quota.per_minute > 0 {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(
+                missing * 60.0 / f64::from(self.quota.per_minute),
+            ))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+
+    /// Gets the address of the client making a request.
+    ///
+    /// The proxy's header is only trusted for the address the proxy set,
+    /// the last of a list, as the client can send the header with any
+    /// addresses before it. Without the header, the peer address is used.
+    fn client(&self, req: &ServiceRequest) -> IpAddr {
+        let proxied = self.client_ip_header.as_ref().and_then(|name| {
+            req.headers()
+                .get(name)?
+                .to_str()
+                .ok()?
+                .rsplit(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok()
+        });
+        let client = proxied
+            .or_else(|| req.peer_addr().map(|addr| addr.ip()))
+            .unwrap_or(UNKNOWN_CLIENT);
+        // Dual-stack sockets see IPv4 clients as mapped IPv6 addresses
+        match client {
+            IpAddr::V6(addr) => addr.to_ipv4_mapped().map_or(client, IpAddr::V4),
+            IpAddr::V4(_) => client,
+        }
+    }
+}
+
+/// Gets the key of a client's bucket: its address, or for IPv6 the
+/// network it is in.
+fn bucket_key(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V6(addr) => {
+            let mask = !0u128 << (128 - IPV6_CLIENT_PREFIX);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+        IpAddr::V4(_) => client,
+    }
+}
+
+/// Middleware that limits how often each client can request a group of
+/// routes, with a token bucket per client.
+///
+/// Buckets are kept in memory and shared by the workers, so the same
+/// middleware must be cloned into every application. Requests over the
+/// limit get a `429 Too Many Requests` with a `Retry-After` header.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl RateLimit {
+    /// Creates a limiter with a quota, or a middleware that does nothing
+    /// without one.
+    pub fn new(quota: Option<Quota>, limits: &RateLimits) -> Self {
+        let limiter = quota.map(|quota| {
+            Arc::new(Limiter {
+                allow: limits.allow.clone(),
+                buckets: Mutex::new(Buckets::new()),
+                client_ip_header: limits
+                    .client_ip_header
+                    .as_ref()
+                    .and_then(|h| HeaderName::from_str(h).ok()),
+                quota,
+            })
+        });
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            limiter: self.limiter.clone(),
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    limiter: Option<Arc<Limiter>>,
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let limited = self
+            .limiter
+            .as_ref()
+            .and_then(|limiter| limiter.check(limiter.client(&req), Instant::now()).err());
+
+        if let Some(wait) = limited {
+            warn!("Rate limited {}", req.path());
+            let retry_after = wait.as_secs_f64().ceil().min(u32::MAX.into()) as u32;
+            let res = HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.max(1).to_string()))
+                .content_type("text/plain")
+                .body("Too many requests");
+            return Box::pin(async move { Ok(req.into_response(res).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App};
+
+    use super::*;
+
+    #[test]
+    fn networks() {
+        let net: Network = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.200.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        assert!(!net.contains("::1".parse().unwrap()));
+
+        let net: Network = "::1".parse().unwrap();
+        assert!(net.contains("::1".parse().unwrap()));
+        assert!(!net.contains("::2".parse().unwrap()));
+
+        let any: Network = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("192.168.1.1".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("localhost".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn token_bucket() {
+        let limiter = Limiter {
+            allow: vec!["127.0.0.1".parse().unwrap()],
+            buckets: Mutex::new(Buckets::new()),
+            client_ip_header: None,
+            quota: Quota {
+                per_minute: 60,
+                burst: 2,
+            },
+        };
+        let client = "192.0.2.1".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(client, start).is_ok());
+        assert!(limiter.check(client, start).is_ok());
+        assert_eq!(limiter.check(client, start), Err(Duration::from_secs(1)));
+
+        // One token per second comes back
+        let later = start + Duration::from_millis(1500);
+        assert!(limiter.check(client, later).is_ok());
+        assert_eq!(
+            limiter.check(client, later),
+            Err(Duration::from_millis(500))
+        );
+
+        // Other and allowed clients have their own limits
+        assert!(limiter.check("192.0.2.2".parse().unwrap(), start).is_ok());
+
+        // IPv6 clients are limited by their /64
+        let v6 = |ip: &str| limiter.check(ip.parse().unwrap(), start);
+        assert!(v6("2001:db8::1").is_ok());
+        assert!(v6("2001:db8::2").is_ok());
+        assert!(v6("2001:db8::3").is_err());
+        assert!(v6("2001:db8:0:1::1").is_ok());
+        for _ in 0..10 {
+            assert!(limiter.check("127.0.0.1".parse().unwrap(), start).is_ok());
+        }
+
+        // Idle clients are forgotten at most once per sweep interval
+        for i in 0..MAX_TRACKED_CLIENTS as u32 {
+            let _ = limiter.check(IpAddr::V4(Ipv4Addr::from(i)), start);
+        }
+        let tracked = || limiter.buckets.lock().unwrap().clients.len();
+        let _ = limiter.check(client, start + Duration::from_secs(1));
+        assert!(tracked() > MAX_TRACKED_CLIENTS);
+        let _ = limiter.check(client, start + SWEEP_INTERVAL * 2);
+        assert_eq!(tracked(), 1);
+    }
+
+    #[actix_web::test]
+    async fn too_many_requests() {
+        let limits = RateLimits {
+            client_ip_header: Some("X-Real-IP".to_string()),
+            ..RateLimits::default()
+        };
+        let quota = Quota {
+            per_minute: 1,
+            burst: 1,
+        };
+        let srv = init_service(
+            App::new().service(
+                web::resource("/")
+                    .wrap(RateLimit::new(Some(quota), &limits))
+                    .to(HttpResponse::Ok),
+            ),
+        )
+        .await;
+        let peer: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let request = |ip: &'static str| {
+            TestRequest::default()
+                .peer_addr(peer)
+                .insert_header(("X-Real-IP", ip))
+                .to_request()
+        };
+
+        let res = call_service(&srv, request("198.51.100.1")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = call_service(&srv, request("198.51.100.1")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "60");
+
+        // Clients are told apart by the proxy's header, not the peer
+        let res = call_service(&srv, request("198.51.100.2")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Addresses before the proxy's own are the client's to choose
+        let res = call_service(&srv, request("203.0.113.9, 198.51.100.2")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // IPv4 clients seen on a dual-stack socket are told apart
+        let res = call_service(&srv, request("::ffff:198.51.100.3")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = call_service(&srv, request("::ffff:198.51.100.4")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = call_service(&srv, request("198.51.100.4")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Without the header, the peer is limited
+        let res = call_service(&srv, TestRequest::default().peer_addr(peer).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = call_service(&srv, request("not an address")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Without an address at all, clients share a bucket
+        let res = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = call_service(&srv, TestRequest::default().to_request()).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,129 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use log::info;
+use uuid::Uuid;
+
+use crate::logging;
+
+/// Header with the ID of a request.
+pub const X_REQUEST_ID: &str = "X-Request-Id";
+
+/// Max length of a request ID taken from a client or proxy.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Middleware that logs a JSON line for every request.
+///
+/// Each request gets an ID, taken from the `X-Request-Id` header set by a
+/// proxy when it is sensible, or generated otherwise. The ID is added to
+/// everything logged while serving the request and returned in the
+/// response's `X-Request-Id` header.
+#[derive(Clone, Default)]
+pub struct RequestLog;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLogMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestLogMiddleware<S> {
+    service: Rc<S>,
+}
+
+/// Gets the ID of a request from its headers, if it is safe to log.
+fn request_id(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(X_REQUEST_ID)?.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    valid.then(|| id.to_string())
+}
+
+impl<S, B> Service<ServiceRequest> for RequestLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let id = request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+        let header = HeaderValue::from_str(&id).unwrap();
+        let service = self.service.clone();
+
+        Box::pin(logging::scope(id, async move {
+            let method = req.method().to_string();
+            let path = req.path().to_string();
+            let mut res = service.call(req).await?;
+
+            let status = res.status();
+            info!(
+                method = method.as_str(),
+                path = path.as_str(),
+                status = status.as_u16(),
+                duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+                "{} {} {}", method, path, status.as_u16()
+            );
+
+            res.headers_mut()
+                .insert(HeaderName::from_static("x-request-id"), header);
+            Ok(res)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn request_id() {
+        let srv = test::init_service(
+            App::new()
+                .wrap(RequestLog)
+                .service(web::resource("/").to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::default()
+            .insert_header((X_REQUEST_ID, "from-proxy.1"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        assert_eq!(res.headers().get(X_REQUEST_ID).unwrap(), "from-proxy.1");
+
+        let req = test::TestRequest::default()
+            .insert_header((X_REQUEST_ID, "<script>"))
+            .to_request();
+        let res = test::call_service(&srv, req).await;
+        let id = res.headers().get(X_REQUEST_ID).unwrap().to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+    }
+}
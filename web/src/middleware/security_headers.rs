@@ -0,0 +1,143 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::responder::ScriptHashes;
+
+/// Content security policy of every response, before the sources of a
+/// page's inline scripts are added to `script-src`.
+///
+/// Everything is served from the site itself: scripts and styles are
+/// static files, and the search box only fetches `/api/search`.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; base-uri 'none'; \
+    form-action 'self'; frame-ancestors 'none'; object-src 'none'; script-src 'self'";
+
+/// Browser features pages never use.
+const PERMISSIONS_POLICY: &str =
+    "camera=(), geolocation=(), microphone=(), payment=(), usb=(), interest-cohort=()";
+
+const REFERRER_POLICY: &str = "strict-origin-when-cross-origin";
+
+/// Middleware that sets the security headers of every response.
+///
+/// Handlers allow the inline scripts of a page (i.e. its JSON-LD) by
+/// adding their [hashes](ScriptHashes) to the response's extensions.
+#[derive(Clone, Default)]
+pub struct SecurityHeaders;
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: Rc<S>,
+}
+
+/// Builds the content security policy allowing a page's inline scripts.
+fn content_security_policy(scripts: Option<&ScriptHashes>) -> String {
+    let mut policy = CONTENT_SECURITY_POLICY.to_string();
+    for source in scripts.map_or(&[][..], ScriptHashes::sources) {
+        policy.push(' ');
+        policy.push_str(source);
+    }
+    policy
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            let policy = content_security_policy(res.response().extensions().get());
+            let headers = res.headers_mut();
+            if let Ok(policy) = HeaderValue::from_str(&policy) {
+                headers.insert(header::CONTENT_SECURITY_POLICY, policy);
+            }
+            headers.insert(
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            );
+            headers.insert(
+                header::REFERRER_POLICY,
+                HeaderValue::from_static(REFERRER_POLICY),
+            );
+            headers.insert(
+                HeaderName::from_static("permissions-policy"),
+                HeaderValue::from_static(PERMISSIONS_POLICY),
+            );
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use sha2::{Digest, Sha256};
+
+    use crate::test::page_response;
+
+    #[actix_web::test]
+    async fn security_headers() {
+        let (headers, body) = page_response("/about").await;
+        let policy = headers
+            .get(header::CONTENT_SECURITY_POLICY)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(policy.starts_with("default-src 'self';"));
+        assert_eq!(
+            headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert!(headers.contains_key(header::REFERRER_POLICY));
+        assert!(headers.contains_key("permissions-policy"));
+
+        // Every inline block of the page is allowed by its hash
+        let blocks = body.split("<script type=\"application/ld+json\">").skip(1);
+        let mut count = 0;
+        for block in blocks {
+            let script = block.split("</script>").next().unwrap();
+            let hash = base64::encode(Sha256::digest(script.as_bytes()));
+            assert!(policy.contains(&format!("'sha256-{}'", hash)));
+            count += 1;
+        }
+        assert_eq!(count, 1);
+
+        // References have blocks for the reference and its breadcrumbs
+        let (headers, _) = page_response("/Psalms/119").await;
+        let policy = headers.get(header::CONTENT_SECURITY_POLICY).unwrap();
+        assert_eq!(policy.to_str().unwrap().matches("'sha256-").count(), 2);
+    }
+}
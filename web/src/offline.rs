@@ -0,0 +1,160 @@
+//! Offline reading of the site, with a service worker precaching the
+//! static files and a bundle of the whole Bible.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use actix_web::web::Bytes;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_derive::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use db::models::{Book, Reference};
+use db::{DbError, SwordDrillable, VerseFormat};
+
+use crate::cache::TRANSLATION;
+use crate::middleware::ContentVersion;
+
+/// Version of the bundle's layout, bumped when it changes.
+const BUNDLE_FORMAT: u32 = 1;
+
+/// Whole translation, for reading chapters that were never visited.
+#[derive(Debug, Serialize)]
+struct Bundle<'a> {
+    format: u32,
+    translation: &'static str,
+    /// Version of the content, changing with the database.
+    version: &'a str,
+    /// Whether the verses are HTML rather than plain text.
+    html: bool,
+    books: &'a [Book],
+    /// Book ID, chapter, verse and text of every verse, in canonical
+    /// order.
+    verses: Vec<(i32, i32, i32, String)>,
+}
+
+/// Offline data of the site: what the service worker caches, and the
+/// bundles of the translation, compressed once when first requested.
+pub struct Offline {
+    /// Name of the service worker's cache, which changes with the content
+    /// and the static files so outdated caches are dropped.
+    pub cache_name: String,
+    /// URLs the service worker caches when installed.
+    pub precache: Vec<String>,
+    /// URL of the text bundle of this version, which is precached.
+    pub bundle_url: String,
+    version: String,
+    text: OnceCell<Bytes>,
+    html: OnceCell<Bytes>,
+}
+
+/// Lists the files in a directory and its subdirectories, sorted.
+fn files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() {
+            names.extend(
+                files(&entry.path())?
+                    .into_iter()
+                    .map(|f| format!("{}/{}", name, f)),
+            );
+        } else {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+impl Offline {
+    /// Hashes the static files to name the cache of the service worker,
+    /// which precaches them, the home page and the text bundle.
+    pub fn new(static_dir: &Path, version: &ContentVersion) -> io::Result<Self> {
+        let version = version.id();
+        let mut hasher = Sha256::new();
+        hasher.update(&version);
+        let mut precache = vec!["/".to_string()];
+        for file in files(static_dir)? {
+            hasher.update(&file);
+            hasher.update(Sha256::digest(fs::read(static_dir.join(&file))?));
+            precache.push(format!("/static/{}", file));
+        }
+        let bundle_url = format!("/api/bundle/{}.json?v={}", TRANSLATION, version);
+        precache.push(bundle_url.clone());
+
+        Ok(Self {
+            cache_name: format!("biblers-{:x}", hasher.finalize())[..24].to_string(),
+            precache,
+            bundle_url,
+            version,
+            text: OnceCell::new(),
+            html: OnceCell::new(),
+        })
+    }
+
+    /// Gets the bundle of the translation in a format, gzipped.
+    pub async fn bundle(
+        &self,
+        drill: &dyn SwordDrillable,
+        books: &[Book],
+        format: VerseFormat,
+    ) -> Result<Bytes, DbError> {
+        let cell = match format {
+            VerseFormat::PlainText => &self.text,
+            VerseFormat::Html => &self.html,
+        };
+        cell.get_or_try_init(|| self.compress(drill, books, format))
+            .await
+            .cloned()
+    }
+
+    /// Builds and compresses a bundle.
+    async fn compress(
+        &self,
+        drill: &dyn SwordDrillable,
+        books: &[Book],
+        format: VerseFormat,
+    ) -> Result<Bytes, DbError> {
+        let mut bundle = Bundle {
+            format: BUNDLE_FORMAT,
+            translation: TRANSLATION,
+            version: &self.version,
+            html: matches!(format, VerseFormat::Html),
+            books,
+            verses: Vec::new(),
+        };
+        for book in books {
+            for chapter in 1..=book.chapter_count {
+                let reference = Reference {
+                    book: book.name.to_owned(),
+                    chapter,
+                    verses: None,
+                };
+                let (_, verses) = drill.verses(&reference, format).await?;
+                bundle.verses.extend(
+                    verses
+                        .into_iter()
+                        .map(|v| (v.book, v.chapter, v.verse, v.words)),
+                );
+            }
+        }
+
+        // Writing to memory doesn't fail
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+        serde_json::to_writer(&mut gzip, &bundle).unwrap();
+        Ok(Bytes::from(gzip.finish().unwrap()))
+    }
+}
+
+/// Decompresses a bundle for a client that doesn't accept gzip.
+pub fn decompress(bundle: &[u8]) -> io::Result<Vec<u8>> {
+    let mut json = Vec::new();
+    GzDecoder::new(bundle).read_to_end(&mut json)?;
+    Ok(json)
+}
@@ -1,4 +1,3 @@
-use actix_web::HttpRequest;
 use handlebars::Handlebars;
 use log::error;
 use serde_derive::{Deserialize, Serialize};
@@ -6,7 +5,8 @@ use serde_derive::{Deserialize, Serialize};
 use db::models::{Book, Reference, Verse, VerseFTS};
 
 use crate::error::Error;
-use crate::responder::link::{verse_url, AllBooksLinks, BookLinks, Link, VersesLinks};
+use crate::format::escape;
+use crate::responder::link::{verse_url, AllBooksLinks, BookLinks, Link, Urls, VersesLinks};
 use crate::responder::meta::Meta;
 
 /// Represents empty data.
@@ -54,7 +54,7 @@ impl VersesData {
         (book, verses): (Book, Vec<Verse>),
         mut reference: Reference,
         books: &[Book],
-        req: &HttpRequest,
+        urls: &impl Urls,
     ) -> Self {
         reference.book = book.name.to_owned();
         reference.verses = if let Some(vs) = reference.verses {
@@ -66,7 +66,7 @@ impl VersesData {
             None
         };
         let reference_string = reference.to_string();
-        let links = VersesLinks::new(&book, &reference, books, req);
+        let links = VersesLinks::new(&book, &reference, books, urls);
 
         Self {
             book,
@@ -88,8 +88,8 @@ pub struct BookData {
 
 impl BookData {
     /// Creates new book data.
-    pub fn new((book, chapters): (Book, Vec<i32>), books: &[Book], req: &HttpRequest) -> Self {
-        let links = BookLinks::new(&book, &chapters, books, req);
+    pub fn new((book, chapters): (Book, Vec<i32>), books: &[Book], urls: &impl Urls) -> Self {
+        let links = BookLinks::new(&book, &chapters, books, urls);
         Self {
             book,
             chapters,
@@ -101,13 +101,13 @@ impl BookData {
 /// Data for the "all books" endpoint (HTML or JSON).
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct AllBooksData {
-    books: Vec<Book>,
+    pub books: Vec<Book>,
     pub links: AllBooksLinks,
 }
 
 impl AllBooksData {
-    pub fn new(books: Vec<Book>, req: &HttpRequest) -> Self {
-        let links = AllBooksLinks::new(books.to_owned(), req);
+    pub fn new(books: Vec<Book>, urls: &impl Urls) -> Self {
+        let links = AllBooksLinks::new(books.to_owned(), urls);
         Self { books, links }
     }
 }
@@ -116,6 +116,8 @@ impl AllBooksData {
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct SearchResult {
     pub link: Link,
+    /// Escaped HTML of the verse, where only matched words are marked up
+    /// (with `<em>`).
     pub text: String,
 }
 
@@ -132,9 +134,9 @@ impl SearchResultData {
     }
 
     /// Creates new search result data from full text search verses.
-    pub fn from_verses_fts(from_db: Vec<(VerseFTS, Book)>, req: &HttpRequest) -> Self {
+    pub fn from_verses_fts(from_db: Vec<(VerseFTS, Book)>, urls: &impl Urls) -> Self {
         let matches = from_db.into_iter().map(|(v, b)| SearchResult {
-            link: verse_url(&b.name, v.chapter, v.verse, req),
+            link: verse_url(&b.name, v.chapter, v.verse, urls),
             text: v.words,
         });
 
@@ -144,11 +146,11 @@ impl SearchResultData {
     }
 
     /// Creates new search result data from standard verses.
-    pub fn from_verses(from_db: (Book, Vec<Verse>), req: &HttpRequest) -> Self {
+    pub fn from_verses(from_db: (Book, Vec<Verse>), urls: &impl Urls) -> Self {
         let name = from_db.0.name;
         let matches = from_db.1.into_iter().map(|v| SearchResult {
-            link: verse_url(&name, v.chapter, v.verse, req),
-            text: v.words,
+            link: verse_url(&name, v.chapter, v.verse, urls),
+            text: escape(&v.words),
         });
 
         Self {
@@ -184,6 +186,7 @@ mod tests {
     use handlebars::Handlebars;
 
     use crate::responder::meta::Meta;
+    use crate::responder::site::Site;
     use crate::test::*;
 
     #[actix_web::test]
@@ -230,11 +233,15 @@ mod tests {
                 chapter: 3,
                 id: 555,
                 verse: 5,
-                words: "Trust in the LORD with all thine heart; and lean not unto thine own understanding.".to_string(),
+                words: "Trust in the LORD <script>alert(1)</script> & lean not".to_string(),
 
             }];
             let data = SearchResultData::from_verses((book, verses), &req);
             assert_eq!(data.matches.len(), 1);
+            assert_eq!(
+                data.matches[0].text,
+                "Trust in the LORD &lt;script&gt;alert(1)&lt;/script&gt; &amp; lean not"
+            );
 
             let results = vec![(VerseFTS {
                 book: 20,
@@ -253,7 +260,7 @@ mod tests {
         let mut tpl = Handlebars::new();
         tpl.register_template_string("test", "<html></html")
             .unwrap();
-        let data = TemplateData::new(EmptyData {}, Meta::for_about());
+        let data = TemplateData::new(EmptyData {}, Meta::for_about(&Site::default()));
         let html = data.to_html("test", &tpl).unwrap();
         assert!(html.starts_with("<html>"));
     }
@@ -3,12 +3,10 @@ use serde_derive::{Deserialize, Serialize};
 
 use db::models::{Book, Reference};
 
-use crate::responder::link::{AllBooksLinks, BookLinks, Link, VersesLinks, NAME};
+use crate::responder::link::{AllBooksLinks, BookLinks, Link, VersesLinks};
+use crate::responder::site::Site;
 
 const CONTEXT: &str = "https://schema.org";
-const CREATOR_FIRST_NAME: &str = "Dustin";
-const CREATOR_LAST_NAME: &str = "Speckhals";
-const CREATOR_URL: &str = "https://speckhals.com";
 const LANGUAGE: &str = "en-us";
 const KEYWORDS: &str = "bible,kjv";
 const VERSION: &str = "King James Version";
@@ -27,16 +25,25 @@ impl ser::Serialize for JsonLd {
     where
         S: ser::Serializer,
     {
-        serializer.serialize_str(
-            &match self {
-                JsonLd::BreadcrumbList(s) => serde_json::to_string_pretty(&s),
-                JsonLd::About(s) => serde_json::to_string_pretty(&s),
-                JsonLd::AllBooks(s) => serde_json::to_string_pretty(&s),
-                JsonLd::Book(s) => serde_json::to_string_pretty(&s),
-                JsonLd::Reference(s) => serde_json::to_string_pretty(&s),
-            }
-            .unwrap(),
-        )
+        serializer.serialize_str(&self.to_script())
+    }
+}
+
+impl JsonLd {
+    /// Gets the JSON of a `<script>` block.
+    ///
+    /// `<` is escaped, so no value (e.g. the configured site name) can
+    /// close the block.
+    pub(super) fn to_script(&self) -> String {
+        match self {
+            JsonLd::BreadcrumbList(s) => serde_json::to_string_pretty(&s),
+            JsonLd::About(s) => serde_json::to_string_pretty(&s),
+            JsonLd::AllBooks(s) => serde_json::to_string_pretty(&s),
+            JsonLd::Book(s) => serde_json::to_string_pretty(&s),
+            JsonLd::Reference(s) => serde_json::to_string_pretty(&s),
+        }
+        .unwrap()
+        .replace('<', "\\u003c")
     }
 }
 
@@ -89,12 +96,12 @@ pub struct ListItemJsonLd {
 }
 
 impl ListItemJsonLd {
-    pub(super) fn new(link: &Link, position: i32) -> Self {
+    pub(super) fn new(site: &Site, link: &Link, position: i32) -> Self {
         Self {
             item: ThingJsonLd {
-                id: format!(url_format!(), link.url),
+                id: site.url_for(&link.url),
                 name: link.label.to_owned(),
-                url: format!(url_format!(), link.url),
+                url: site.url_for(&link.url),
                 kind: match position {
                     1 => Kind::BookSeries,
                     2 => Kind::Book,
@@ -146,10 +153,10 @@ impl Default for ThingJsonLd {
     fn default() -> Self {
         Self {
             context: CONTEXT.to_string(),
-            id: format!(url_format!(), ""),
+            id: String::new(),
             kind: Kind::Thing,
             name: "Default".to_string(),
-            url: format!(url_format!(), ""),
+            url: String::new(),
         }
     }
 }
@@ -167,24 +174,24 @@ pub struct AboutJsonLd {
 }
 
 impl AboutJsonLd {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(site: &Site) -> Self {
         let person_thing = ThingJsonLd {
-            id: CREATOR_URL.to_string(),
+            id: site.creator.url.to_owned(),
             kind: Kind::Person,
-            name: format!("{} {}", CREATOR_FIRST_NAME, CREATOR_LAST_NAME),
-            url: CREATOR_URL.to_string(),
+            name: site.creator.name.to_owned(),
+            url: site.creator.url.to_owned(),
             ..ThingJsonLd::default()
         };
         let creator = PersonJsonLd {
             thing: person_thing,
-            family_name: CREATOR_LAST_NAME.to_string(),
-            given_name: CREATOR_FIRST_NAME.to_string(),
+            family_name: site.creator.family_name().to_string(),
+            given_name: site.creator.given_name().to_string(),
         };
         let thing = ThingJsonLd {
-            id: format!(url_format!(), "/about"),
+            id: site.url_for("/about"),
             kind: Kind::Website,
-            name: NAME.to_string(),
-            url: format!(url_format!(), "/about"),
+            name: site.name.to_owned(),
+            url: site.url_for("/about"),
             ..ThingJsonLd::default()
         };
 
@@ -209,19 +216,19 @@ pub struct AllBooksJsonLd {
 }
 
 impl AllBooksJsonLd {
-    pub(super) fn new(links: &AllBooksLinks) -> Self {
+    pub(super) fn new(site: &Site, links: &AllBooksLinks) -> Self {
         let has_part = links
             .books
             .iter()
             .map(|b| PartJsonLd {
-                id: format!(url_format!(), b.url),
+                id: site.url_for(&b.url),
             })
             .collect();
         let thing = ThingJsonLd {
-            id: format!(url_format!(), ""),
+            id: site.url_for(""),
             kind: Kind::BookSeries,
-            name: NAME.to_string(),
-            url: format!(url_format!(), ""),
+            name: site.name.to_owned(),
+            url: site.url_for(""),
             ..ThingJsonLd::default()
         };
 
@@ -247,22 +254,22 @@ pub struct BookJsonLd {
 }
 
 impl BookJsonLd {
-    pub(super) fn new(book: &Book, links: &BookLinks) -> Self {
+    pub(super) fn new(site: &Site, book: &Book, links: &BookLinks) -> Self {
         let has_part = links
             .chapters
             .iter()
             .map(|c| PartJsonLd {
-                id: format!(url_format!(), c),
+                id: site.url_for(c),
             })
             .collect();
         let is_part_of = PartJsonLd {
-            id: format!(url_format!(), links.books.url),
+            id: site.url_for(&links.books.url),
         };
         let thing = ThingJsonLd {
-            id: format!(url_format!(), links.current.url),
+            id: site.url_for(&links.current.url),
             kind: Kind::Book,
             name: book.name.to_owned(),
-            url: format!(url_format!(), links.current.url),
+            url: site.url_for(&links.current.url),
             ..ThingJsonLd::default()
         };
 
@@ -287,16 +294,16 @@ pub struct ReferenceJsonLd {
 }
 
 impl ReferenceJsonLd {
-    pub(super) fn new(reference: &Reference, links: &VersesLinks) -> Self {
+    pub(super) fn new(site: &Site, reference: &Reference, links: &VersesLinks) -> Self {
         let thing = ThingJsonLd {
-            id: format!(url_format!(), links.current.url),
+            id: site.url_for(&links.current.url),
             kind: Kind::Chapter,
             name: reference.to_string(),
-            url: format!(url_format!(), links.current.url),
+            url: site.url_for(&links.current.url),
             ..ThingJsonLd::default()
         };
         let is_part_of = PartJsonLd {
-            id: format!(url_format!(), links.book.url),
+            id: site.url_for(&links.book.url),
         };
 
         Self {
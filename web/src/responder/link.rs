@@ -1,5 +1,6 @@
 use std::ops::RangeInclusive;
 
+use actix_web::dev::ResourceDef;
 use actix_web::error::UrlGenerationError;
 use actix_web::HttpRequest;
 use log::error;
@@ -11,54 +12,129 @@ use db::models::{Book, Reference};
 /// Name used in the HTML title generator
 pub const NAME: &str = "Bible.rs";
 
-fn invalid_url(e: UrlGenerationError) -> Url {
-    error!("{:?}", e);
-    Url::parse("https://bible.rs").unwrap()
+/// Route of a book's page.
+pub const BOOK_ROUTE: &str = "{book}";
+
+/// Route of a reference's page.
+pub const REFERENCE_ROUTE: &str = "{reference:.+\\d}";
+
+/// Route of a book's sitemap.
+pub const SITEMAP_BOOK_ROUTE: &str = "sitemap/{book}.xml";
+
+/// Generator of the URLs of named routes, which pages link to.
+pub trait Urls {
+    fn url_for(&self, name: &str, elements: &[&str]) -> Result<Url, UrlGenerationError>;
+}
+
+impl Urls for HttpRequest {
+    fn url_for(&self, name: &str, elements: &[&str]) -> Result<Url, UrlGenerationError> {
+        HttpRequest::url_for(self, name, elements)
+    }
+}
+
+/// URLs of the pages' routes, for rendering pages outside of a request.
+///
+/// Links only keep the path of a URL, so pages link the same way as when
+/// rendered for a request.
+pub struct RoutePaths {
+    routes: Vec<(&'static str, ResourceDef)>,
+}
+
+impl RoutePaths {
+    pub fn new() -> Self {
+        let routes = [
+            ("about", "/about".to_string()),
+            ("bible", "/".to_string()),
+            ("book", format!("/{}", BOOK_ROUTE)),
+            ("reference", format!("/{}", REFERENCE_ROUTE)),
+            ("sitemap", "/sitemap.xml".to_string()),
+            ("sitemap_book", format!("/{}", SITEMAP_BOOK_ROUTE)),
+            ("sitemap_pages", "/sitemap/pages.xml".to_string()),
+        ];
+        Self {
+            routes: routes
+                .into_iter()
+                .map(|(name, path)| (name, ResourceDef::new(path)))
+                .collect(),
+        }
+    }
+}
+
+impl Default for RoutePaths {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Urls for RoutePaths {
+    fn url_for(&self, name: &str, elements: &[&str]) -> Result<Url, UrlGenerationError> {
+        let route = match self.routes.iter().find(|(n, _)| *n == name) {
+            Some((_, route)) => route,
+            None => return Err(UrlGenerationError::ResourceNotFound),
+        };
+        let mut path = String::new();
+        if !route.resource_path_from_iter(&mut path, elements.iter()) {
+            return Err(UrlGenerationError::NotEnoughElements);
+        }
+        Ok(Url::parse(&format!("http://localhost{}", path))?)
+    }
+}
+
+/// Creates a link from a generated URL, falling back to the root path
+/// when the URL couldn't be generated.
+fn link(url: Result<Url, UrlGenerationError>, label: String) -> Link {
+    match url {
+        Ok(url) => Link::new(&url, label),
+        Err(e) => {
+            error!("{:?}", e);
+            Link {
+                label,
+                url: "/".to_string(),
+            }
+        }
+    }
 }
 
 /// Generates a book URL for the given book.
-fn book_url(b: &str, req: &HttpRequest) -> Link {
-    Link::new(
-        &req.url_for("book", [b]).unwrap_or_else(invalid_url),
-        b.to_string(),
-    )
+fn book_url(b: &str, urls: &impl Urls) -> Link {
+    link(urls.url_for("book", &[b]), b.to_string())
 }
 
 /// Generates a chapter URL for the given book and chapter.
-fn chapter_url(b: &str, c: i32, req: &HttpRequest) -> Link {
+fn chapter_url(b: &str, c: i32, urls: &impl Urls) -> Link {
     let chapter_string = c.to_string();
-    Link::new(
-        &req.url_for("reference", [format!("{}/{}", b, chapter_string)])
-            .unwrap_or_else(invalid_url),
+    link(
+        urls.url_for("reference", &[&format!("{}/{}", b, chapter_string)]),
         format!("{} {}", b, chapter_string),
     )
 }
 
 /// Generates a verse URL from the given book, chapter, and verse.
-pub(super) fn verse_url(b: &str, c: i32, v: i32, req: &HttpRequest) -> Link {
+pub(super) fn verse_url(b: &str, c: i32, v: i32, urls: &impl Urls) -> Link {
     let chapter_string = c.to_string();
     let verse_string = v.to_string();
-    let mut url = req
-        .url_for("reference", [format!("{}/{}", b, chapter_string)])
-        .unwrap_or_else(invalid_url);
-    url.set_fragment(Some(&format!("v{}", verse_string)));
-    Link::new(&url, format!("{} {}:{}", b, chapter_string, verse_string))
+    let url = urls
+        .url_for("reference", &[&format!("{}/{}", b, chapter_string)])
+        .map(|mut url| {
+            url.set_fragment(Some(&format!("v{}", verse_string)));
+            url
+        });
+    link(url, format!("{} {}:{}", b, chapter_string, verse_string))
 }
 
 /// Generates a URL for verses from the given book, chapter, and verse range.
-fn verse_range_url(b: &str, c: i32, verses: &RangeInclusive<i32>, req: &HttpRequest) -> Link {
+fn verse_range_url(b: &str, c: i32, verses: &RangeInclusive<i32>, urls: &impl Urls) -> Link {
     let chapter_string = c.to_string();
     let verses_string = if verses.start() == verses.end() {
         verses.start().to_string()
     } else {
         format!("{}-{}", verses.start(), verses.end())
     };
-    Link::new(
-        &req.url_for(
+    link(
+        urls.url_for(
             "reference",
-            [format!("{}/{}/{}", b, chapter_string, verses_string)],
-        )
-        .unwrap_or_else(invalid_url),
+            &[&format!("{}/{}/{}", b, chapter_string, verses_string)],
+        ),
         format!("{} {}:{}", b, chapter_string, verses_string),
     )
 }
@@ -102,87 +178,22 @@ impl VersesLinks {
         book: &Book,
         reference: &Reference,
         books: &[Book],
-        req: &HttpRequest,
+        urls: &impl Urls,
     ) -> Self {
-        let bible_root = Link::new(&req.url_for_static("bible").unwrap(), NAME.to_string());
-        let book_index = book.id as usize - 1;
-        println!("{}", book_index);
+        let bible_root = Link::new(&urls.url_for("bible", &[]).unwrap(), NAME.to_string());
         let book_link = Link::new(
-            &req.url_for("book", [&book.name]).unwrap(),
+            &urls.url_for("book", &[&book.name]).unwrap(),
             book.name.to_string(),
         );
-        let chapter_link = Some(chapter_url(&book.name, reference.chapter, req));
+        let chapter_link = Some(chapter_url(&book.name, reference.chapter, urls));
         let current_link = match reference.verses {
-            Some(ref vs) => verse_range_url(&book.name, reference.chapter, vs, req),
-            None => chapter_url(&book.name, reference.chapter, req),
+            Some(ref vs) => verse_range_url(&book.name, reference.chapter, vs, urls),
+            None => chapter_url(&book.name, reference.chapter, urls),
         };
 
-        let (prev_link, next_link) = {
-            let c = reference.chapter;
-            let previous = if book_index == 0 {
-                None
-            } else {
-                books.get(book_index - 1)
-            };
-            let next = books.get(book_index + 1);
-
-            // First chapter in the Bible
-            // Previous: nothing
-            // Next: Genesis 2
-            if previous.is_none() && c == 1 {
-                (None, Some(chapter_url(&book.name, c + 1, req)))
-
-            // Last chapter in the Bible
-            // Previous: Revelation 21
-            // Next: nothing
-            } else if next.is_none() && c == book.chapter_count {
-                (
-                    Some(chapter_url(&book.name, book.chapter_count - 1, req)),
-                    None,
-                )
-
-            // Books with one chapter
-            // Previous: last chapter of the previous book
-            // Next: first chapter of the last book
-            } else if book.chapter_count == 1 {
-                (
-                    Some(chapter_url(
-                        &previous.unwrap().name,
-                        previous.unwrap().chapter_count,
-                        req,
-                    )),
-                    Some(chapter_url(&next.unwrap().name, 1, req)),
-                )
-            // First chapter in a book
-            // Previous: last chapter of the previous book
-            // Next: next chapter in same book
-            } else if c == 1 {
-                (
-                    Some(chapter_url(
-                        &previous.unwrap().name,
-                        previous.unwrap().chapter_count,
-                        req,
-                    )),
-                    Some(chapter_url(&book.name, c + 1, req)),
-                )
-            // Last chapter in a book
-            // Previous: last chapter in same book
-            // Next: first chapter in next book
-            } else if c == book.chapter_count {
-                (
-                    Some(chapter_url(&book.name, c - 1, req)),
-                    Some(chapter_url(&next.unwrap().name, 1, req)),
-                )
-            // Everything else
-            // Previous: last chapter in same book
-            // Next: next chapter in same book
-            } else {
-                (
-                    Some(chapter_url(&book.name, c - 1, req)),
-                    Some(chapter_url(&book.name, c + 1, req)),
-                )
-            }
-        };
+        let (previous, next) = book.adjacent_chapters(reference.chapter, books);
+        let prev_link = previous.map(|(b, c)| chapter_url(&b.name, c, urls));
+        let next_link = next.map(|(b, c)| chapter_url(&b.name, c, urls));
 
         Self {
             books: bible_root,
@@ -207,25 +218,25 @@ pub struct BookLinks {
 
 impl BookLinks {
     /// Creates a new structure of book links.
-    pub(super) fn new(book: &Book, chapters: &[i32], books: &[Book], req: &HttpRequest) -> Self {
+    pub(super) fn new(book: &Book, chapters: &[i32], books: &[Book], urls: &impl Urls) -> Self {
         let book_index = book.id as usize - 1;
         Self {
-            books: Link::new(&req.url_for_static("bible").unwrap(), NAME.to_string()),
+            books: Link::new(&urls.url_for("bible", &[]).unwrap(), NAME.to_string()),
             chapters: chapters
                 .iter()
-                .map(|c| chapter_url(&book.name, *c, req).url)
+                .map(|c| chapter_url(&book.name, *c, urls).url)
                 .collect(),
             previous: if book.id != 1 {
-                Some(book_url(&books[book_index - 1].name, req))
+                Some(book_url(&books[book_index - 1].name, urls))
             } else {
                 None
             },
             next: if book.id != books.len() as i32 {
-                Some(book_url(&books[book_index + 1].name, req))
+                Some(book_url(&books[book_index + 1].name, urls))
             } else {
                 None
             },
-            current: book_url(&book.name, req),
+            current: book_url(&book.name, urls),
         }
     }
 }
@@ -236,9 +247,9 @@ pub struct AllBooksLinks {
 }
 
 impl AllBooksLinks {
-    pub(super) fn new(books: Vec<Book>, req: &HttpRequest) -> Self {
+    pub(super) fn new(books: Vec<Book>, urls: &impl Urls) -> Self {
         Self {
-            books: books.iter().map(|b| book_url(&b.name, req)).collect(),
+            books: books.iter().map(|b| book_url(&b.name, urls)).collect(),
         }
     }
 }
@@ -330,6 +341,17 @@ mod tests {
             assert_eq!(links.current.url, "/Psalms/119");
             assert_eq!(links.previous.unwrap().url, "/Psalms/118");
             assert_eq!(links.next.unwrap().url, "/Psalms/120");
+
+            // Rendered outside of a request
+            let reference: Reference = "Song of Solomon 2:3-5".parse().unwrap();
+            let book = BOOKS[21].clone();
+            let with_request = VersesLinks::new(&book, &reference, &BOOKS, &req);
+            let without = VersesLinks::new(&book, &reference, &BOOKS, &RoutePaths::new());
+            assert_eq!(without.current.url, "/Song%20of%20Solomon/2/3-5");
+            assert_eq!(
+                serde_json::to_string(&without).unwrap(),
+                serde_json::to_string(&with_request).unwrap()
+            );
         })
         .await;
     }
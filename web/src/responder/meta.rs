@@ -1,88 +1,147 @@
 use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use db::models::{Book, Reference, Verse};
 
 use crate::responder::json_ld::*;
 use crate::responder::link::{AllBooksLinks, BookLinks, VersesLinks};
+use crate::responder::site::Site;
+
+/// Path of the image shown when a page is shared.
+const IMAGE_PATH: &str = "/static/img/bible.rs-512x512.png";
+
+/// Hashes of a page's inline scripts, as content security policy sources
+/// (e.g. `'sha256-...'`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScriptHashes(Vec<String>);
+
+impl ScriptHashes {
+    /// Hashes the contents of inline scripts.
+    pub fn new<S: AsRef<str>>(scripts: impl IntoIterator<Item = S>) -> Self {
+        Self(
+            scripts
+                .into_iter()
+                .map(|s| {
+                    let digest = Sha256::digest(s.as_ref().as_bytes());
+                    format!("'sha256-{}'", base64::encode(digest))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn sources(&self) -> &[String] {
+        &self.0
+    }
+}
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Meta {
     description: String,
+    image: String,
     json_ld: Vec<JsonLd>,
+    site_name: String,
     title: String,
     url: String,
 }
 
 impl Meta {
-    pub fn for_about() -> Self {
+    /// Creates metadata common to every page of a site.
+    fn new(
+        site: &Site,
+        title: &str,
+        path: &str,
+        description: String,
+        json_ld: Vec<JsonLd>,
+    ) -> Self {
         Self {
-            description: "About Bible.rs".to_string(),
-            json_ld: vec![JsonLd::About(Box::new(AboutJsonLd::new()))],
-            title: format!(title_format!(), "About"),
-            url: format!(url_format!(), "/about"),
+            description,
+            image: site.url_for(IMAGE_PATH),
+            json_ld,
+            site_name: site.name.to_owned(),
+            title: format!("{} | {}", site.name, title),
+            url: site.url_for(path),
         }
     }
 
-    pub fn for_all_books(links: &AllBooksLinks) -> Self {
-        Self {
-            description: "Browse and search the King James version of the Bible using a lightning-fast and slick interface.".to_string(),
-            json_ld: vec![JsonLd::AllBooks(AllBooksJsonLd::new(links))],
-            title: format!(title_format!(), "King James Version"),
-            url: format!(url_format!(), ""),
-        }
+    /// Gets the hashes of the JSON-LD blocks rendered in `base.hbs`.
+    pub fn script_hashes(&self) -> ScriptHashes {
+        ScriptHashes::new(self.json_ld.iter().map(JsonLd::to_script))
     }
 
-    pub fn for_book(book: &Book, links: &BookLinks) -> Self {
-        Self {
-            description: format!("The book of {}", book.name),
-            json_ld: vec![
-                JsonLd::Book(BookJsonLd::new(book, links)),
+    pub fn for_about(site: &Site) -> Self {
+        Self::new(
+            site,
+            "About",
+            "/about",
+            format!("About {}", site.name),
+            vec![JsonLd::About(Box::new(AboutJsonLd::new(site)))],
+        )
+    }
+
+    pub fn for_all_books(site: &Site, links: &AllBooksLinks) -> Self {
+        Self::new(
+            site,
+            "King James Version",
+            "",
+            "Browse and search the King James version of the Bible using a lightning-fast and slick interface.".to_string(),
+            vec![JsonLd::AllBooks(AllBooksJsonLd::new(site, links))],
+        )
+    }
+
+    pub fn for_book(site: &Site, book: &Book, links: &BookLinks) -> Self {
+        Self::new(
+            site,
+            &book.name,
+            &links.current.url,
+            format!("The book of {}", book.name),
+            vec![
+                JsonLd::Book(BookJsonLd::new(site, book, links)),
                 JsonLd::BreadcrumbList(BreadcrumbListJsonLd::new(vec![
-                    ListItemJsonLd::new(&links.books, 1),
-                    ListItemJsonLd::new(&links.current, 2),
+                    ListItemJsonLd::new(site, &links.books, 1),
+                    ListItemJsonLd::new(site, &links.current, 2),
                 ])),
             ],
-            title: format!(title_format!(), book.name),
-            url: format!(url_format!(), links.current.url),
-        }
+        )
     }
 
-    pub fn for_error() -> Self {
-        Self {
-            description: "Error page".to_string(),
-            json_ld: vec![],
-            title: format!(title_format!(), "Error"),
-            url: format!(url_format!(), ""),
-        }
+    pub fn for_error(site: &Site) -> Self {
+        Self::new(site, "Error", "", "Error page".to_string(), vec![])
     }
 
-    pub fn for_reference(reference: &Reference, verses: &[Verse], links: &VersesLinks) -> Self {
+    pub fn for_reference(
+        site: &Site,
+        reference: &Reference,
+        verses: &[Verse],
+        links: &VersesLinks,
+    ) -> Self {
         let ref_string = reference.to_string();
-        Self {
-            description: match verses.first() {
+        Self::new(
+            site,
+            &ref_string,
+            &links.current.url,
+            match verses.first() {
                 None => ref_string.to_owned(),
                 Some(v) => format!("{}...", v.words),
             },
-            json_ld: vec![
-                JsonLd::Reference(ReferenceJsonLd::new(reference, links)),
+            vec![
+                JsonLd::Reference(ReferenceJsonLd::new(site, reference, links)),
                 JsonLd::BreadcrumbList(BreadcrumbListJsonLd::new(vec![
-                    ListItemJsonLd::new(&links.books, 1),
-                    ListItemJsonLd::new(&links.book, 2),
-                    ListItemJsonLd::new(&links.current, 3),
+                    ListItemJsonLd::new(site, &links.books, 1),
+                    ListItemJsonLd::new(site, &links.book, 2),
+                    ListItemJsonLd::new(site, &links.current, 3),
                 ])),
             ],
-            title: format!(title_format!(), ref_string),
-            url: format!(url_format!(), links.current.url),
-        }
+        )
     }
 
-    pub fn for_search(query: &str, url: &str) -> Self {
+    pub fn for_search(site: &Site, query: &str, url: &str) -> Self {
         let results_string = format!("Results for '{}'", query);
-        Self {
-            description: results_string.to_owned(),
-            json_ld: vec![],
-            title: format!(title_format!(), results_string),
-            url: format!(url_format!(), url),
-        }
+        Self::new(
+            site,
+            &results_string,
+            url,
+            results_string.to_owned(),
+            vec![],
+        )
     }
 }
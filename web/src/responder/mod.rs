@@ -2,6 +2,15 @@ mod data;
 mod json_ld;
 mod link;
 mod meta;
+mod site;
+mod text;
 
 pub use crate::responder::data::*;
-pub use crate::responder::meta::Meta;
+pub use crate::responder::link::{
+    RoutePaths, Urls, BOOK_ROUTE, REFERENCE_ROUTE, SITEMAP_BOOK_ROUTE,
+};
+pub use crate::responder::meta::{Meta, ScriptHashes};
+pub use crate::responder::site::{
+    Creator, Site, DEFAULT_CREATOR_NAME, DEFAULT_CREATOR_URL, DEFAULT_SITE_NAME, DEFAULT_SITE_URL,
+};
+pub use crate::responder::text::ToText;
@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+
+use actix_web::HttpRequest;
+use url::Url;
+
+/// Canonical URL of the site.
+pub const DEFAULT_SITE_URL: &str = "https://bible.rs";
+
+/// Name of the site.
+pub const DEFAULT_SITE_NAME: &str = "Bible.rs";
+
+/// Name of the site's creator.
+pub const DEFAULT_CREATOR_NAME: &str = "Dustin Speckhals";
+
+/// URL of the site's creator.
+pub const DEFAULT_CREATOR_URL: &str = "https://speckhals.com";
+
+/// Person who created the site, credited in the about page's JSON-LD.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Creator {
+    pub name: String,
+    pub url: String,
+}
+
+impl Creator {
+    /// First word of the creator's name.
+    pub fn given_name(&self) -> &str {
+        self.name.split_whitespace().next().unwrap_or_default()
+    }
+
+    /// Everything after the first word of the creator's name.
+    pub fn family_name(&self) -> &str {
+        let name = self.name.trim();
+        name.split_once(char::is_whitespace)
+            .map_or("", |(_, family)| family.trim_start())
+    }
+}
+
+/// Identity of the site pages are generated for.
+///
+/// Canonical links, `og:` tags and JSON-LD are all built from the site's
+/// base URL, so a mirror can be served under its own domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Site {
+    pub creator: Creator,
+    /// Whether the base URL is taken from each request's `Forwarded` or
+    /// `Host` headers instead of `url`.
+    pub from_request: bool,
+    pub name: String,
+    pub url: Url,
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Self {
+            creator: Creator {
+                name: DEFAULT_CREATOR_NAME.to_string(),
+                url: DEFAULT_CREATOR_URL.to_string(),
+            },
+            from_request: false,
+            name: DEFAULT_SITE_NAME.to_string(),
+            url: Url::parse(DEFAULT_SITE_URL).unwrap(),
+        }
+    }
+}
+
+impl Site {
+    /// Gets the absolute URL of a path on the site.
+    pub fn url_for(&self, path: &str) -> String {
+        format!("{}{}", self.url.as_str().trim_end_matches('/'), path)
+    }
+
+    /// Gets the site as seen by a request.
+    ///
+    /// When the site is derived from requests, the scheme and host come
+    /// from the `Forwarded` (or `X-Forwarded-*`) and `Host` headers.
+    pub fn for_request(&self, req: &HttpRequest) -> Cow<'_, Site> {
+        if !self.from_request {
+            return Cow::Borrowed(self);
+        }
+
+        let url = {
+            let info = req.connection_info();
+            Url::parse(&format!("{}://{}", info.scheme(), info.host()))
+        };
+        match url {
+            Ok(url) => Cow::Owned(Site {
+                url,
+                ..self.to_owned()
+            }),
+            Err(_) => Cow::Borrowed(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn url_for() {
+        let site = Site::default();
+        assert_eq!(site.url_for(""), "https://bible.rs");
+        assert_eq!(site.url_for("/John/3"), "https://bible.rs/John/3");
+
+        let site = Site {
+            url: Url::parse("https://example.com/bible/").unwrap(),
+            ..Site::default()
+        };
+        assert_eq!(site.url_for("/John/3"), "https://example.com/bible/John/3");
+
+        assert_eq!(site.creator.given_name(), "Dustin");
+        assert_eq!(site.creator.family_name(), "Speckhals");
+    }
+
+    #[test]
+    fn for_request() {
+        let req = TestRequest::default()
+            .insert_header((header::FORWARDED, "proto=https;host=mirror.example"))
+            .to_http_request();
+
+        assert_eq!(
+            Site::default().for_request(&req).url_for(""),
+            "https://bible.rs"
+        );
+
+        let site = Site {
+            from_request: true,
+            ..Site::default()
+        };
+        assert_eq!(site.for_request(&req).url_for(""), "https://mirror.example");
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::Write;
+
+use crate::responder::data::{AllBooksData, BookData, VersesData};
+use crate::responder::link::{Link, NAME};
+
+/// Data that can be rendered as plain text or Markdown.
+pub trait ToText {
+    /// Renders the data as plain text, in the same style as the CLI.
+    fn to_plain_text(&self) -> String;
+
+    /// Renders the data as a Markdown document.
+    fn to_markdown(&self) -> String;
+}
+
+/// Renders a Markdown inline link.
+fn md_link(link: &Link) -> String {
+    format!("[{}]({})", link.label, link.url)
+}
+
+/// Renders the previous/next links of a page as a Markdown line.
+fn md_pager(previous: &Option<Link>, next: &Option<Link>) -> Option<String> {
+    match (previous, next) {
+        (None, None) => None,
+        (Some(p), None) => Some(format!("← {}", md_link(p))),
+        (None, Some(n)) => Some(format!("{} →", md_link(n))),
+        (Some(p), Some(n)) => Some(format!("← {} | {} →", md_link(p), md_link(n))),
+    }
+}
+
+impl ToText for VersesData {
+    fn to_plain_text(&self) -> String {
+        let mut out = format!("{}\n", self.reference_string);
+        for v in &self.verses {
+            let _ = writeln!(out, "{}\t{}", v.verse, v.words);
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.reference_string);
+        for v in &self.verses {
+            let _ = writeln!(out, "**{}** {}\n", v.verse, v.words);
+        }
+        if let Some(pager) = md_pager(&self.links.previous, &self.links.next) {
+            let _ = writeln!(out, "{}", pager);
+        }
+        out
+    }
+}
+
+impl ToText for BookData {
+    fn to_plain_text(&self) -> String {
+        let mut out = format!("{}\n", self.book.name);
+        for c in &self.chapters {
+            let _ = writeln!(out, "{} {}", self.book.name, c);
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.book.name);
+        for (c, url) in self.chapters.iter().zip(&self.links.chapters) {
+            let _ = writeln!(out, "- [{} {}]({})", self.book.name, c, url);
+        }
+        if let Some(pager) = md_pager(&self.links.previous, &self.links.next) {
+            let _ = write!(out, "\n{}\n", pager);
+        }
+        out
+    }
+}
+
+impl ToText for AllBooksData {
+    fn to_plain_text(&self) -> String {
+        self.books.iter().fold(String::new(), |mut out, b| {
+            let _ = writeln!(out, "{}", b.name);
+            out
+        })
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", NAME);
+        for link in &self.links.books {
+            let _ = writeln!(out, "- {}", md_link(link));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use db::models::{Reference, Verse};
+
+    use super::*;
+    use crate::test::*;
+
+    #[actix_web::test]
+    async fn verses_text() {
+        with_service(|req| {
+            let book = BOOKS[42].clone();
+            let verses = vec![Verse {
+                book: 43,
+                chapter: 3,
+                id: 26_137,
+                verse: 16,
+                words: "For God so loved the world, that he gave his only begotten Son, that whosoever believeth in him should not perish, but have everlasting life.".to_string(),
+            }];
+            let reference: Reference = "John 3:16".parse().unwrap();
+            let data = VersesData::new((book, verses), reference, &BOOKS, &req);
+
+            let plain = data.to_plain_text();
+            assert!(plain.starts_with("John 3:16\n16\tFor God so loved the world"));
+
+            let markdown = data.to_markdown();
+            assert!(markdown.starts_with("# John 3:16\n\n**16** For God so loved the world"));
+            assert!(markdown.contains("← [John 2](/John/2) | [John 4](/John/4) →"));
+        })
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn book_text() {
+        with_service(|req| {
+            let book = BOOKS[64].clone();
+            let data = BookData::new((book, vec![1]), &BOOKS, &req);
+
+            assert_eq!(data.to_plain_text(), "Jude\nJude 1\n");
+            assert!(data.to_markdown().contains("- [Jude 1](/Jude/1)"));
+        })
+        .await;
+    }
+}
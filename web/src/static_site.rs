@@ -0,0 +1,266 @@
+//! Static copy of the site, to host on any static file host.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use actix_web::web::{self, Bytes};
+use log::info;
+use serde_derive::Serialize;
+
+use db::models::{Book, Reference};
+use db::{SwordDrillable, VerseFormat};
+
+use crate::cache::TRANSLATION;
+use crate::config::Config;
+use crate::controllers::{pwa, sitemap, view, Representation};
+use crate::error::Error;
+use crate::offline;
+use crate::responder::RoutePaths;
+use crate::ServerData;
+
+/// Name of the search index in the static files.
+pub const SEARCH_INDEX: &str = "search-index.json";
+
+/// Every verse of the Bible, searched by the pages when there is no
+/// search API to query.
+#[derive(Debug, Serialize)]
+struct SearchIndex {
+    /// Names of the books, in canonical order.
+    books: Vec<String>,
+    /// Index of the book, chapter, verse and text of each verse.
+    verses: Vec<(usize, i32, i32, String)>,
+}
+
+/// Page the server generates.
+enum SitePage<'a> {
+    AllBooks,
+    About,
+    Robots,
+    SitemapIndex,
+    SitemapPages,
+    ServiceWorker,
+    Bundle,
+    SitemapBook(&'a Book),
+    Book(&'a Book),
+    Chapter(&'a Book, i32),
+}
+
+impl SitePage<'_> {
+    /// Path of the page on the site.
+    fn path(&self) -> String {
+        match self {
+            Self::AllBooks => "/".to_string(),
+            Self::About => "/about".to_string(),
+            Self::Robots => "/robots.txt".to_string(),
+            Self::SitemapIndex => "/sitemap.xml".to_string(),
+            Self::SitemapPages => "/sitemap/pages.xml".to_string(),
+            Self::ServiceWorker => "/sw.js".to_string(),
+            Self::Bundle => format!("/api/bundle/{}.json", TRANSLATION),
+            Self::SitemapBook(book) => format!("/sitemap/{}.xml", book.name),
+            Self::Book(book) => format!("/{}", book.name),
+            Self::Chapter(book, chapter) => format!("/{}/{}", book.name, chapter),
+        }
+    }
+
+    /// Renders the page as a client is served it, from the same
+    /// templates, metadata and structured data.
+    async fn render(&self, data: &ServerData, urls: &RoutePaths) -> Result<Bytes, Error> {
+        let site = &data.site;
+        let html = Representation::Html;
+        let document = |result: Result<String, _>| {
+            result
+                .map(Bytes::from)
+                .map_err(|e| Error::Export(format!("{:?}", e)))
+        };
+        Ok(match self {
+            Self::AllBooks => view::all_books_page(data, html, site, urls).await?.body,
+            Self::About => view::about_page(data, site)?.body,
+            Self::Robots => document(sitemap::robots_text(site, urls))?,
+            Self::SitemapIndex => document(sitemap::index_document(&data.books, site, urls))?,
+            Self::SitemapPages => document(sitemap::pages_document(site, urls))?,
+            Self::ServiceWorker => Bytes::from(pwa::service_worker_script(data)?),
+            Self::Bundle => {
+                let bundle = data
+                    .offline
+                    .bundle(data.drill.as_ref(), &data.books, VerseFormat::PlainText)
+                    .await?;
+                Bytes::from(offline::decompress(&bundle).map_err(|e| Error::Export(e.to_string()))?)
+            }
+            Self::SitemapBook(book) => document(sitemap::book_document(book, site, urls))?,
+            Self::Book(book) => {
+                view::book_page(data, &book.name, html, site, urls)
+                    .await?
+                    .body
+            }
+            Self::Chapter(book, chapter) => {
+                let reference = Reference {
+                    book: book.name.to_owned(),
+                    chapter: *chapter,
+                    verses: None,
+                };
+                match view::reference_page(data, reference, html, site, urls).await? {
+                    Some((page, _)) => page.body,
+                    None => return Err(Error::NotFound(self.path())),
+                }
+            }
+        })
+    }
+}
+
+/// Pages the server generates: the site's own pages, every book and
+/// chapter, the sitemaps pointing crawlers at them, and the service
+/// worker with the bundle it caches for offline reading.
+fn site_pages(books: &[Book]) -> Vec<SitePage<'_>> {
+    let mut pages = vec![
+        SitePage::AllBooks,
+        SitePage::About,
+        SitePage::Robots,
+        SitePage::SitemapIndex,
+        SitePage::SitemapPages,
+        SitePage::ServiceWorker,
+        SitePage::Bundle,
+    ];
+    for book in books {
+        pages.push(SitePage::SitemapBook(book));
+        pages.push(SitePage::Book(book));
+        for chapter in 1..=book.chapter_count {
+            pages.push(SitePage::Chapter(book, chapter));
+        }
+    }
+    pages
+}
+
+/// File a page is written to in the output directory.
+///
+/// Pages without an extension are written as HTML files next to their
+/// directory (e.g. `John.html` and `John/3.html`), which static hosts
+/// serve at the same URLs as the server does.
+fn page_file(out: &Path, path: &str) -> PathBuf {
+    match path.trim_start_matches('/') {
+        "" => out.join("index.html"),
+        path if Path::new(path).extension().is_some() => out.join(path),
+        path => out.join(format!("{}.html", path)),
+    }
+}
+
+/// Copies a directory and everything in it.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds the search index of every verse.
+async fn search_index(drill: &dyn SwordDrillable, books: &[Book]) -> io::Result<SearchIndex> {
+    let mut index = SearchIndex {
+        books: books.iter().map(|b| b.name.to_owned()).collect(),
+        verses: Vec::new(),
+    };
+    for (i, book) in books.iter().enumerate() {
+        for chapter in 1..=book.chapter_count {
+            let reference = Reference {
+                book: book.name.to_owned(),
+                chapter,
+                verses: None,
+            };
+            let (_, verses) = drill
+                .verses(&reference, VerseFormat::PlainText)
+                .await
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            index
+                .verses
+                .extend(verses.into_iter().map(|v| (i, v.chapter, v.verse, v.words)));
+        }
+    }
+    Ok(index)
+}
+
+/// Renders every page of the site into a directory, with the static
+/// files and a search index.
+///
+/// The pages are rendered by the same functions as the application's
+/// routes, linking to the configured site. Returns the number of pages.
+pub async fn build(
+    app_data: web::Data<ServerData>,
+    config: &Config,
+    out: &Path,
+) -> io::Result<usize> {
+    let urls = RoutePaths::new();
+    let pages = site_pages(&app_data.books);
+    for page in &pages {
+        let path = page.path();
+        let body = page
+            .render(&app_data, &urls)
+            .await
+            .map_err(|e| io::Error::other(format!("Could not render {}: {}", path, e)))?;
+
+        let file = page_file(out, &path);
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(file, body)?;
+    }
+    info!("Rendered {} pages into {}", pages.len(), out.display());
+
+    let static_dir = out.join("static");
+    copy_dir(&config.static_dir, &static_dir)?;
+    let index = search_index(app_data.drill.as_ref(), &app_data.books).await?;
+    fs::write(
+        static_dir.join(SEARCH_INDEX),
+        serde_json::to_vec(&index).map_err(io::Error::from)?,
+    )?;
+    info!("Indexed {} verses for search", index.verses.len());
+
+    Ok(pages.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{html_response, page_data, BOOKS};
+
+    #[test]
+    fn paths() {
+        let paths: Vec<String> = site_pages(&BOOKS).iter().map(SitePage::path).collect();
+        assert_eq!(paths.len(), 7 + 66 * 2 + 1189);
+        assert!(paths.contains(&"/Song of Solomon/8".to_string()));
+
+        let out = Path::new("site");
+        assert_eq!(page_file(out, "/"), out.join("index.html"));
+        assert_eq!(page_file(out, "/about"), out.join("about.html"));
+        assert_eq!(page_file(out, "/1 John/5"), out.join("1 John/5.html"));
+        assert_eq!(
+            page_file(out, "/sitemap/John.xml"),
+            out.join("sitemap/John.xml")
+        );
+    }
+
+    #[actix_web::test]
+    async fn rendered_as_served() {
+        let data = page_data();
+        let urls = RoutePaths::new();
+        let book = &BOOKS[18];
+        for page in [
+            SitePage::AllBooks,
+            SitePage::About,
+            SitePage::Robots,
+            SitePage::SitemapIndex,
+            SitePage::SitemapPages,
+            SitePage::SitemapBook(book),
+            SitePage::Book(book),
+        ] {
+            let path = page.path();
+            let body = page.render(&data, &urls).await.unwrap();
+            assert_eq!(body, html_response(&path).await, "{}", path);
+        }
+    }
+}
@@ -1,15 +1,27 @@
+use std::path::Path;
 use std::str;
 
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
 use actix_web::{test, web, App, HttpRequest, HttpResponse};
+use async_trait::async_trait;
 use handlebars::Handlebars;
 use lazy_static::lazy_static;
 use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
 
 use db::models::*;
 use db::*;
 
-use crate::ServerData;
-use crate::{api, view};
+use crate::cache::{EpubCache, PageCache};
+use crate::error;
+use crate::metrics::Metrics;
+use crate::middleware::{ContentVersion, SecurityHeaders};
+use crate::offline::Offline;
+use crate::responder::{Site, BOOK_ROUTE, REFERENCE_ROUTE, SITEMAP_BOOK_ROUTE};
+use crate::{api, download, health, pwa, sitemap, view};
+use crate::{metrics_routes, ServerData};
 
 pub async fn with_service<F>(f: F)
 where
@@ -22,8 +34,8 @@ where
                 HttpResponse::Ok()
             }))
             .service(web::resource("/").name("bible"))
-            .service(web::resource("{book}").name("book"))
-            .service(web::resource("{reference:.+\\d}").name("reference")),
+            .service(web::resource(BOOK_ROUTE).name("book"))
+            .service(web::resource(REFERENCE_ROUTE).name("reference")),
     );
 
     test::call_service(
@@ -44,12 +56,9 @@ fn test_book() -> Book {
 
 pub struct TestSwordDrill;
 
+#[async_trait]
 impl SwordDrillable for TestSwordDrill {
-    fn verses(
-        _: &Reference,
-        _: VerseFormat,
-        _: &mut DbConnection,
-    ) -> Result<(Book, Vec<Verse>), DbError> {
+    async fn verses(&self, _: &Reference, _: VerseFormat) -> Result<(Book, Vec<Verse>), DbError> {
         let book = test_book();
 
         let verse = Verse {
@@ -63,15 +72,77 @@ impl SwordDrillable for TestSwordDrill {
         Ok((book, vec![verse]))
     }
 
-    fn book(_: &str, _: &mut DbConnection) -> Result<(Book, Vec<i32>), DbError> {
+    async fn words(&self, _: &Reference) -> Result<(Book, Vec<Word>), DbError> {
+        let words = "NUN. Thy word is a lamp unto my feet, and a light unto my path."
+            .split(' ')
+            .enumerate()
+            .map(|(i, w)| {
+                let (word, punctuation) = match w.strip_suffix(['.', ',']) {
+                    Some(word) => (word, Some(w[word.len()..].to_string())),
+                    None => (w, None),
+                };
+                Word {
+                    id: i as i32,
+                    book: 19,
+                    chapter: 119,
+                    paragraph: 1,
+                    verse: 105,
+                    position: i as i32 + 1,
+                    word: word.to_string(),
+                    punctuation,
+                    italic: word == "is",
+                    close_parentheses: false,
+                    open_parentheses: false,
+                }
+            })
+            .collect();
+
+        Ok((test_book(), words))
+    }
+
+    async fn book(&self, book_name: &str) -> Result<(Book, Vec<i32>), DbError> {
+        if !BOOKS.iter().any(|b| b.name.eq_ignore_ascii_case(book_name)) {
+            return Err(DbError::BookNotFound {
+                book: book_name.to_string(),
+            });
+        }
         Ok((test_book(), (1..=150).collect()))
     }
 
-    fn all_books(_: &mut DbConnection) -> Result<Vec<Book>, DbError> {
+    async fn all_books(&self) -> Result<Vec<Book>, DbError> {
         Ok(vec![test_book()])
     }
 
-    fn search(_: &str, _: &mut DbConnection) -> Result<Vec<(VerseFTS, Book)>, DbError> {
+    async fn verse_ids(&self) -> Result<Vec<i32>, DbError> {
+        Ok(vec![555])
+    }
+
+    async fn verse(&self, _: i32) -> Result<(Book, Verse), DbError> {
+        let (book, mut verses) = self
+            .verses(&"Psalms 119:105".parse().unwrap(), VerseFormat::PlainText)
+            .await?;
+        Ok((book, verses.remove(0)))
+    }
+
+    async fn ready(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    fn stream_verses(&self) -> mpsc::Receiver<Result<Verse, DbError>> {
+        let (tx, rx) = mpsc::channel(2);
+        for verse in [104, 105] {
+            let _ = tx.try_send(Ok(Verse {
+                id: verse,
+                book: 19,
+                chapter: 119,
+                verse,
+                words: format!("Verse {}, \"quoted\".", verse),
+            }));
+        }
+        rx
+    }
+
+    async fn search(&self, _: &str) -> Result<Vec<(VerseFTS, Book)>, DbError> {
         let book = test_book();
         let verse = VerseFTS {
             book: 19,
@@ -86,67 +157,186 @@ impl SwordDrillable for TestSwordDrill {
     }
 }
 
+/// Offline data of the static files in `./dist`.
+fn test_offline() -> Offline {
+    Offline::new(Path::new("./dist"), &ContentVersion::new("1,2", 3))
+        .expect("Could not hash the static files")
+}
+
 pub async fn json_response<T>(uri: &str) -> T
 where
     T: DeserializeOwned,
 {
     let srv = test::init_service(
         App::new()
-            .app_data(web::Data::new(ServerData {
-                books: BOOKS.to_vec(),
-                db: build_pool(":memory:"),
-                template: Handlebars::default(),
-            }))
+            .app_data(web::Data::new(page_data()))
             .service(web::resource("/").name("bible"))
-            .service(web::resource("{book}").name("book"))
-            .service(web::resource("{reference:.+\\d}").name("reference"))
-            .service(web::resource("api/search").to(api::search::<TestSwordDrill>))
-            .service(web::resource("api/{reference}.json").to(api::reference::<TestSwordDrill>)),
+            .service(web::resource(BOOK_ROUTE).name("book"))
+            .service(web::resource(REFERENCE_ROUTE).name("reference"))
+            .service(web::resource("api/search").to(api::search))
+            .service(web::resource("api/{reference}.json").to(api::reference)),
     );
 
     let req = test::TestRequest::with_uri(uri).to_request();
     test::call_and_read_body_json(&srv.await, req).await
 }
 
+/// Gets the status, headers and body of a download.
+pub async fn download_response(uri: &str) -> (StatusCode, HeaderMap, Bytes) {
+    error::register_error_pages(Path::new("./templates/"), &Site::default())
+        .expect("Could not register the error pages");
+    let srv = test::init_service(
+        App::new()
+            .app_data(web::Data::new(page_data()))
+            .service(web::resource("api/export/{translation}.epub").to(download::epub))
+            .service(
+                web::resource("api/export/{translation}.{format:txt|csv|tsv|jsonl}")
+                    .to(download::text),
+            ),
+    );
+
+    let req = test::TestRequest::with_uri(uri).to_request();
+    let res = test::call_service(&srv.await, req).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    (status, headers, test::read_body(res).await)
+}
+
+/// Gets the status, headers and body of the service worker or a bundle,
+/// accepting the given encodings.
+pub async fn offline_response(
+    uri: &str,
+    accept_encoding: Option<&str>,
+) -> (StatusCode, HeaderMap, Bytes) {
+    error::register_error_pages(Path::new("./templates/"), &Site::default())
+        .expect("Could not register the error pages");
+    // The bundle holds the verses of every book, which the test drill
+    // only has for Psalms.
+    let data = ServerData {
+        books: vec![test_book()],
+        ..page_data()
+    };
+    let srv = test::init_service(
+        App::new()
+            .app_data(web::Data::new(data))
+            .service(web::resource("sw.js").to(pwa::service_worker))
+            .service(web::resource("api/bundle/{translation}.json").to(pwa::bundle)),
+    );
+
+    let mut req = test::TestRequest::with_uri(uri);
+    if let Some(encodings) = accept_encoding {
+        req = req.insert_header((header::ACCEPT_ENCODING, encodings));
+    }
+    let res = test::call_service(&srv.await, req.to_request()).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    (status, headers, test::read_body(res).await)
+}
+
 pub async fn html_response(uri: &str) -> String {
+    negotiated_response(uri, "text/html").await
+}
+
+pub async fn negotiated_response(uri: &str, accept: &str) -> String {
+    let (_, _, body) = response(uri, accept).await;
+    body
+}
+
+/// Gets the status of a page of the site.
+pub async fn page_status(uri: &str) -> StatusCode {
+    let (status, _, _) = response(uri, "text/html").await;
+    status
+}
+
+/// Gets the headers and body of an HTML page.
+pub async fn page_response(uri: &str) -> (HeaderMap, String) {
+    let (_, headers, body) = response(uri, "text/html").await;
+    (headers, body)
+}
+
+/// Gets the status and body of a response of the metrics server.
+pub async fn metrics_response(uri: &str) -> (StatusCode, String) {
+    let srv = test::init_service(
+        App::new()
+            .app_data(web::Data::new(page_data()))
+            .configure(metrics_routes),
+    );
+
+    let req = test::TestRequest::with_uri(uri).to_request();
+    let res = test::call_service(&srv.await, req).await;
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Data of a server rendering pages with the templates, caching them.
+pub fn page_data() -> ServerData {
     let mut template = Handlebars::new();
     template.set_strict_mode(true);
     template
         .register_templates_directory(".hbs", "./templates/")
         .expect("Could not register template files");
 
+    ServerData {
+        books: BOOKS.to_vec(),
+        drill: Box::new(TestSwordDrill),
+        epubs: EpubCache::new(&ContentVersion::new("1,2", 3)),
+        metrics: Metrics::new(),
+        offline: test_offline(),
+        pages: PageCache::new(1 << 20, &Site::default()),
+        site: Site::default(),
+        template,
+    }
+}
+
+async fn response(uri: &str, accept: &str) -> (StatusCode, HeaderMap, String) {
     let srv = test::init_service(
         App::new()
-            .app_data(web::Data::new(ServerData {
-                books: BOOKS.to_vec(),
-                db: build_pool(":memory:"),
-                template,
-            }))
-            .service(web::resource("about").to(view::about))
+            .wrap(SecurityHeaders)
+            .app_data(web::Data::new(page_data()))
+            .service(web::resource("about").name("about").to(view::about))
+            .service(web::resource("healthz").to(health::healthz))
+            .service(web::resource("readyz").to(health::readyz))
+            .service(web::resource("robots.txt").to(sitemap::robots))
+            .service(
+                web::resource("sitemap.xml")
+                    .name("sitemap")
+                    .to(sitemap::index),
+            )
             .service(
-                web::resource("/")
-                    .name("bible")
-                    .to(view::all_books::<TestSwordDrill>),
+                web::resource("sitemap/pages.xml")
+                    .name("sitemap_pages")
+                    .to(sitemap::pages),
             )
             .service(
-                web::resource("{book}")
-                    .name("book")
-                    .to(view::book::<TestSwordDrill>),
+                web::resource(SITEMAP_BOOK_ROUTE)
+                    .name("sitemap_book")
+                    .to(sitemap::book),
             )
+            .service(web::resource("/").name("bible").to(view::all_books))
+            .service(web::resource("search").name("search").to(view::search))
+            .service(web::resource(BOOK_ROUTE).name("book").to(view::book))
             .service(
-                web::resource("{reference:.+\\d}")
+                web::resource(REFERENCE_ROUTE)
                     .name("reference")
-                    .to(view::reference::<TestSwordDrill>),
+                    .to(view::reference),
             )
-            .service(web::resource("api/search").to(api::search::<TestSwordDrill>))
-            .service(web::resource("api/{reference}.json").to(api::reference::<TestSwordDrill>)),
+            .service(web::resource("api/search").to(api::search))
+            .service(web::resource("api/{reference}.json").to(api::reference)),
     );
 
-    let req = test::TestRequest::with_uri(uri).to_request();
+    let req = test::TestRequest::with_uri(uri)
+        .insert_header((header::ACCEPT, accept))
+        .to_request();
 
-    str::from_utf8(&test::call_and_read_body(&srv.await, req).await)
+    let res = test::call_service(&srv.await, req).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = str::from_utf8(&test::read_body(res).await)
         .expect("Could not convert response to UTF8")
-        .to_string()
+        .to_string();
+
+    (status, headers, body)
 }
 
 lazy_static! {
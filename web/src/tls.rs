@@ -0,0 +1,309 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::{error, info};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{Certificate, PrivateKey, ServerConfig, SignatureScheme};
+use rustls_pemfile::Item;
+use thiserror::Error;
+
+/// How often the certificate files are checked for renewals.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Message signed with a private key to check it matches a certificate.
+const KEY_PROBE: &[u8] = b"Bible.rs certificate key check";
+
+/// Signature schemes a private key can be checked with, and how the
+/// certificate verifies them.
+const PROBE_SCHEMES: [(SignatureScheme, &webpki::SignatureAlgorithm); 4] = [
+    (
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        &webpki::ECDSA_P256_SHA256,
+    ),
+    (
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        &webpki::ECDSA_P384_SHA384,
+    ),
+    (SignatureScheme::ED25519, &webpki::ED25519),
+    (
+        SignatureScheme::RSA_PSS_SHA256,
+        &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    ),
+];
+
+/// Error loading a certificate and its private key.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Could not read '{}': {}", path.display(), cause)]
+    Read { path: PathBuf, cause: io::Error },
+
+    #[error("No certificate found in '{}'", .0.display())]
+    NoCertificate(PathBuf),
+
+    #[error("No private key found in '{}'", .0.display())]
+    NoPrivateKey(PathBuf),
+
+    #[error("Unsupported private key in '{}'", .0.display())]
+    UnsupportedKey(PathBuf),
+
+    #[error("The private key in '{}' doesn't match the certificate in '{}'", key.display(), cert.display())]
+    KeyMismatch { cert: PathBuf, key: PathBuf },
+}
+
+/// Reads the PEM items of a file.
+fn read_pem(path: &Path) -> Result<Vec<Item>, TlsError> {
+    let read_error = |cause| TlsError::Read {
+        path: path.to_owned(),
+        cause,
+    };
+    let file = File::open(path).map_err(read_error)?;
+    rustls_pemfile::read_all(&mut BufReader::new(file)).map_err(read_error)
+}
+
+/// Loads a certificate chain and its private key.
+fn load_certified_key(cert: &Path, key: &Path) -> Result<CertifiedKey, TlsError> {
+    let certs: Vec<_> = read_pem(cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(cert.to_owned()));
+    }
+
+    let private_key = read_pem(key)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError::NoPrivateKey(key.to_owned()))?;
+    let signing_key = sign::any_supported_type(&private_key)
+        .map_err(|_| TlsError::UnsupportedKey(key.to_owned()))?;
+
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    if !keys_match(&certified_key) {
+        return Err(TlsError::KeyMismatch {
+            cert: cert.to_owned(),
+            key: key.to_owned(),
+        });
+    }
+    Ok(certified_key)
+}
+
+/// Whether the private key is the certificate's, by verifying a signature
+/// made with the key against the certificate's public key.
+fn keys_match(certified_key: &CertifiedKey) -> bool {
+    let schemes = PROBE_SCHEMES.map(|(scheme, _)| scheme);
+    let Some(signer) = certified_key.key.choose_scheme(&schemes) else {
+        return false;
+    };
+    let Some(&(_, algorithm)) = PROBE_SCHEMES.iter().find(|(s, _)| *s == signer.scheme()) else {
+        return false;
+    };
+    let Ok(signature) = signer.sign(KEY_PROBE) else {
+        return false;
+    };
+
+    webpki::EndEntityCert::try_from(certified_key.cert[0].0.as_slice())
+        .and_then(|cert| cert.verify_signature(algorithm, KEY_PROBE, &signature))
+        .is_ok()
+}
+
+/// Gets when a file was last modified, if it can be told.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Certificate served for every connection, reloaded when its files
+/// change (e.g. when certbot renews it).
+pub struct CertResolver {
+    cert: PathBuf,
+    key: PathBuf,
+    current: RwLock<(Option<SystemTime>, Arc<CertifiedKey>)>,
+}
+
+impl CertResolver {
+    /// Loads the certificate chain and private key in PEM files.
+    pub fn load(cert: &Path, key: &Path) -> Result<Self, TlsError> {
+        let modified = Self::modified(cert, key);
+        let certified_key = load_certified_key(cert, key)?;
+
+        Ok(Self {
+            cert: cert.to_owned(),
+            key: key.to_owned(),
+            current: RwLock::new((modified, Arc::new(certified_key))),
+        })
+    }
+
+    /// Latest modification time of the certificate and key files.
+    fn modified(cert: &Path, key: &Path) -> Option<SystemTime> {
+        modified(cert).max(modified(key))
+    }
+
+    /// Reloads the certificate if its files changed since it was loaded.
+    ///
+    /// Returns whether it was reloaded. The current certificate is kept
+    /// when the new files can't be loaded, or the key isn't the
+    /// certificate's (e.g. while they are written).
+    pub fn reload(&self) -> Result<bool, TlsError> {
+        let modified = Self::modified(&self.cert, &self.key);
+        if modified == self.current.read().unwrap().0 {
+            return Ok(false);
+        }
+
+        let certified_key = load_certified_key(&self.cert, &self.key)?;
+        *self.current.write().unwrap() = (modified, Arc::new(certified_key));
+        Ok(true)
+    }
+
+    /// Checks for renewed certificates in the background.
+    pub fn watch(self: &Arc<Self>) {
+        let resolver = Arc::clone(self);
+        thread::spawn(move || loop {
+            thread::sleep(RELOAD_INTERVAL);
+            match resolver.reload() {
+                Ok(true) => info!("Reloaded TLS certificate '{}'", resolver.cert.display()),
+                Ok(false) => {}
+                Err(e) => error!("Could not reload TLS certificate: {}", e),
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.current.read().unwrap().1))
+    }
+}
+
+/// Builds the TLS configuration of the server.
+///
+/// Actix adds the ALPN protocols when binding, so HTTP/2 is negotiated
+/// with the clients supporting it.
+pub fn server_config(resolver: Arc<CertResolver>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver)
+}
+
+/// Handles plain HTTP requests by redirecting them to HTTPS on the given
+/// port.
+pub async fn redirect_to_https(req: HttpRequest, https_port: web::Data<u16>) -> HttpResponse {
+    let info = req.connection_info();
+    let host = info.host();
+    // Drop the port of the host, keeping IPv6 addresses whole
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    let port = match **https_port {
+        443 => String::new(),
+        port => format!(":{}", port),
+    };
+    let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+
+    HttpResponse::MovedPermanently()
+        .insert_header((
+            header::LOCATION,
+            format!("https://{}{}{}", host, port, path),
+        ))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+
+    use super::*;
+
+    const CERT: &str = "./tests/fixtures/localhost.crt";
+    const KEY: &str = "./tests/fixtures/localhost.key";
+    const OTHER_KEY: &str = "./tests/fixtures/other.key";
+
+    #[test]
+    fn reload() {
+        let dir = env::temp_dir().join(format!("biblers-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        fs::copy(CERT, &cert).unwrap();
+        fs::copy(KEY, &key).unwrap();
+
+        let touch = |secs| {
+            let file = File::options().write(true).open(&key).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(secs))
+                .unwrap();
+        };
+
+        let resolver = CertResolver::load(&cert, &key).unwrap();
+        assert!(!resolver.reload().unwrap());
+
+        // A half-written renewal keeps the current certificate
+        fs::write(&key, "").unwrap();
+        touch(60);
+        assert!(matches!(resolver.reload(), Err(TlsError::NoPrivateKey(_))));
+
+        // So does a key that isn't the certificate's
+        fs::copy(OTHER_KEY, &key).unwrap();
+        touch(90);
+        assert!(matches!(
+            resolver.reload(),
+            Err(TlsError::KeyMismatch { .. })
+        ));
+
+        fs::copy(KEY, &key).unwrap();
+        touch(120);
+        assert!(resolver.reload().unwrap());
+        assert!(!resolver.reload().unwrap());
+
+        assert!(matches!(
+            CertResolver::load(&key, &key),
+            Err(TlsError::NoCertificate(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn redirect() {
+        let redirect = |port: u16| {
+            init_service(
+                App::new()
+                    .app_data(web::Data::new(port))
+                    .default_service(web::to(redirect_to_https)),
+            )
+        };
+
+        let srv = redirect(443).await;
+        let req = TestRequest::with_uri("/John/3/16?x=1")
+            .insert_header((header::HOST, "bible.rs:80"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            "https://bible.rs/John/3/16?x=1"
+        );
+
+        let srv = redirect(8443).await;
+        let req = TestRequest::with_uri("/")
+            .insert_header((header::HOST, "[::1]:8080"))
+            .to_request();
+        let res = call_service(&srv, req).await;
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            "https://[::1]:8443/"
+        );
+    }
+}